@@ -0,0 +1,305 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io;
+
+use crate::best::{relative_score, RELATIVE_SCALE};
+use crate::history::{self, RunRecord};
+use crate::{format_with_commas, CaseResult, Objective, Score};
+
+/// 比較対象となる過去の実行結果。
+pub struct Baseline {
+    /// 比較対象の実行 ID。
+    pub id: String,
+    /// ケース番号ごとのスコア。失敗したケースは None。
+    scores: HashMap<u32, Option<Score>>,
+    objective: Objective,
+}
+
+/// 1ケースの比較結果。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CaseDiff {
+    /// ベースラインのスコア。失敗していた場合は None。
+    pub base: Option<Score>,
+    /// 今回のスコア。失敗した場合は None。
+    pub current: Option<Score>,
+    /// 今回の方が良ければ Greater。失敗は常に成功より悪いとみなす。
+    pub ordering: Ordering,
+}
+
+impl CaseDiff {
+    pub fn new(current: Option<Score>, base: Option<Score>, objective: Objective) -> Self {
+        let ordering = match (current, base) {
+            (Some(c), Some(b)) => objective.compare(c, b),
+            (Some(_), None) => Ordering::Greater,
+            (None, Some(_)) => Ordering::Less,
+            (None, None) => Ordering::Equal,
+        };
+        Self { base, current, ordering }
+    }
+
+    /// 今回のスコア - ベースラインのスコア。どちらかが失敗していれば None。
+    pub fn delta(&self) -> Option<Score> {
+        Some(self.current? - self.base?)
+    }
+
+    /// 相対スコアの差 (今回 - ベースライン)。2つのうち良い方を満点 1.0 とし、失敗は 0 とする。
+    /// 今回の方が良ければ正で、-1.0〜1.0 の値になる。両方失敗していれば None。
+    pub fn relative_diff(&self, objective: Objective) -> Option<f64> {
+        let best = match (self.current, self.base) {
+            (Some(c), Some(b)) => {
+                if objective.is_better(b, c) {
+                    b
+                } else {
+                    c
+                }
+            }
+            (Some(s), None) | (None, Some(s)) => s,
+            (None, None) => return None,
+        };
+        let relative = |s: Option<Score>| s.map_or(0.0, |s| relative_score(s, best, objective) as f64 / RELATIVE_SCALE);
+        Some(relative(self.current) - relative(self.base))
+    }
+
+    /// 今回のスコア / ベースラインのスコア。
+    pub fn ratio(&self) -> Option<f64> {
+        let base = self.base?.as_f64();
+        if base == 0.0 {
+            return None;
+        }
+        Some(self.current?.as_f64() / base)
+    }
+}
+
+/// スコア合計の比較。失敗を 0 点とすると最小化で失敗が多い方が良く見えるので、
+/// 両方で成功したケースだけの合計を比べる。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TotalDiff {
+    pub diff: CaseDiff,
+    /// 合計に含めたケース (両方で成功したもの) の数。
+    pub compared: usize,
+    /// 比較したケースの数。
+    pub cases: usize,
+}
+
+impl TotalDiff {
+    pub fn new<'a>(diffs: impl IntoIterator<Item = &'a CaseDiff>, objective: Objective) -> Self {
+        let (mut current, mut base) = (Vec::new(), Vec::new());
+        let mut cases = 0;
+        for d in diffs {
+            cases += 1;
+            if let (Some(c), Some(b)) = (d.current, d.base) {
+                current.push(c);
+                base.push(b);
+            }
+        }
+        let compared = current.len();
+        let diff = CaseDiff::new(Some(current.iter().sum()), Some(base.iter().sum()), objective);
+        Self { diff, compared, cases }
+    }
+
+    /// "+1,234 (101.23%) over 9/10 cases" の形式にする。
+    pub fn format(&self) -> String {
+        format!("{} over {}/{} cases", format_diff(&self.diff), self.compared, self.cases)
+    }
+}
+
+/// ベースラインとの勝ち/負け/引き分けの集計。
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct WinLoss {
+    pub win: usize,
+    pub lose: usize,
+    pub draw: usize,
+}
+
+impl WinLoss {
+    pub fn add(&mut self, diff: &CaseDiff) {
+        match diff.ordering {
+            Ordering::Greater => self.win += 1,
+            Ordering::Less => self.lose += 1,
+            Ordering::Equal => self.draw += 1,
+        }
+    }
+}
+
+impl Baseline {
+    pub fn from_record(record: &RunRecord, objective: Objective) -> Self {
+        Self {
+            id: record.id.clone(),
+            scores: record.cases.iter().map(|r| (r.case, r.ok_score())).collect(),
+            objective,
+        }
+    }
+
+    /// 指定に従ってベースラインを履歴から選ぶ。
+    /// - "previous": 直前の実行
+    /// - "best": `cases` を失敗せずに解けた数が最も多く、その中でスコア合計が最も良い実行
+    /// - それ以外: 実行 ID
+    pub fn resolve(out_dir: &str, spec: &str, cases: &[u32], objective: Objective) -> io::Result<Self> {
+        let not_found = || io::Error::new(io::ErrorKind::NotFound, "no runs in history");
+        match spec {
+            "previous" => {
+                let records = history::load_all(out_dir)?;
+                records.last().map(|r| Self::from_record(r, objective)).ok_or_else(not_found)
+            }
+            "best" => {
+                let records = history::load_all(out_dir)?;
+                records
+                    .iter()
+                    .map(|r| Self::from_record(r, objective))
+                    .max_by(|a, b| {
+                        a.solved(cases)
+                            .cmp(&b.solved(cases))
+                            .then_with(|| objective.compare(a.total_over(cases), b.total_over(cases)))
+                    })
+                    .ok_or_else(not_found)
+            }
+            id => Ok(Self::from_record(&history::load(out_dir, id)?, objective)),
+        }
+    }
+
+    /// 過去の実行 `record` と比べるベースラインを選ぶ。
+    /// "previous" は `record` より前の実行のうち最も新しいもの、それ以外は `resolve` と同じ。
+    pub fn resolve_for(out_dir: &str, spec: &str, record: &RunRecord, objective: Objective) -> io::Result<Self> {
+        if spec != "previous" {
+            let cases: Vec<u32> = record.cases.iter().map(|r| r.case).collect();
+            return Self::resolve(out_dir, spec, &cases, objective);
+        }
+        // ID を文字列として比べると "-10" が "-2" より前になるので、load_all の並び順で直前のものを取る
+        let records = history::load_all(out_dir)?;
+        records
+            .iter()
+            .position(|r| r.id == record.id)
+            .and_then(|i| i.checked_sub(1))
+            .map(|i| Self::from_record(&records[i], objective))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no run before '{}'", record.id)))
+    }
+
+    /// ケースのスコア。ベースラインに無いケースは None、失敗していたケースは Some(None)。
+    pub fn score(&self, case: u32) -> Option<Option<Score>> {
+        self.scores.get(&case).copied()
+    }
+
+    /// 指定したケースのうち、ベースラインで成功していたものの数。
+    fn solved(&self, cases: &[u32]) -> usize {
+        cases.iter().filter(|c| self.score(**c).flatten().is_some()).count()
+    }
+
+    /// 指定したケースのスコア合計。ベースラインに無いケースや失敗したケースは含めない。
+    pub fn total_over(&self, cases: &[u32]) -> Score {
+        cases.iter().filter_map(|c| self.score(*c).flatten()).collect::<Vec<_>>().iter().sum()
+    }
+
+    /// ベースラインにあるケースについて、両方で成功したケースだけのスコア合計を比べる。
+    pub fn total_diff(&self, results: &[CaseResult]) -> TotalDiff {
+        let diffs: Vec<CaseDiff> = results.iter().filter_map(|r| self.diff(r)).collect();
+        TotalDiff::new(&diffs, self.objective)
+    }
+
+    /// ケースの結果をベースラインと比較する。ベースラインに無いケースは None。
+    pub fn diff(&self, result: &CaseResult) -> Option<CaseDiff> {
+        let base = self.score(result.case)?;
+        Some(CaseDiff::new(result.ok_score(), base, self.objective))
+    }
+}
+
+/// 比較結果を "+1,234 (101.23%)" の形式にする。
+/// どちらかが失敗している場合はベースラインの状態を表示する。
+pub fn format_diff(diff: &CaseDiff) -> String {
+    let Some(delta) = diff.delta() else {
+        return match diff.base {
+            Some(b) => format!("base {}", format_with_commas(b)),
+            None => "base FAILED".to_string(),
+        };
+    };
+    let sign = if delta.as_f64() < 0.0 { "" } else { "+" };
+    let delta = format!("{}{}", sign, format_with_commas(delta));
+    match diff.ratio() {
+        Some(r) => format!("{} ({:.2}%)", delta, r * 100.0),
+        None => delta,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_case_diff() {
+        let d = CaseDiff::new(Some(Score::Int(1100)), Some(Score::Int(1000)), Objective::Max);
+        assert_eq!(d.delta(), Some(Score::Int(100)));
+        assert_eq!(d.ordering, Ordering::Greater);
+        assert_eq!(format_diff(&d), "+100 (110.00%)");
+
+        let d = CaseDiff::new(Some(Score::Int(1100)), Some(Score::Int(1000)), Objective::Min);
+        assert_eq!(d.ordering, Ordering::Less);
+
+        let d = CaseDiff::new(Some(Score::Int(0)), Some(Score::Int(1234)), Objective::Max);
+        assert_eq!(format_diff(&d), "-1,234 (0.00%)");
+
+        let d = CaseDiff::new(Some(Score::Int(5)), Some(Score::Int(0)), Objective::Max);
+        assert_eq!(format_diff(&d), "+5");
+
+        let d = CaseDiff::new(Some(Score::Float(1.5)), Some(Score::Float(2.0)), Objective::Min);
+        assert_eq!(format_diff(&d), "-0.5 (75.00%)");
+        assert_eq!(d.ordering, Ordering::Greater);
+
+        let d = CaseDiff::new(None, Some(Score::Int(10)), Objective::Min);
+        assert_eq!(d.ordering, Ordering::Less);
+        assert_eq!(format_diff(&d), "base 10");
+    }
+
+    #[test]
+    fn test_win_loss() {
+        let mut wl = WinLoss::default();
+        for (cur, base) in [(Some(2), Some(1)), (Some(1), Some(1)), (None, Some(1)), (Some(3), None)] {
+            wl.add(&CaseDiff::new(cur.map(Score::Int), base.map(Score::Int), Objective::Max));
+        }
+        assert_eq!(wl, WinLoss { win: 2, lose: 1, draw: 1 });
+    }
+
+    #[test]
+    fn test_total_diff() {
+        // 最小化で今回だけ失敗したケースは合計に含めない (0 点として足すと良くなったように見える)
+        let diffs: Vec<CaseDiff> = [(Some(10), Some(12)), (None, Some(100)), (Some(5), None), (Some(7), Some(7))]
+            .into_iter()
+            .map(|(c, b)| CaseDiff::new(c.map(Score::Int), b.map(Score::Int), Objective::Min))
+            .collect();
+        let t = TotalDiff::new(&diffs, Objective::Min);
+        assert_eq!((t.diff.current, t.diff.base), (Some(Score::Int(17)), Some(Score::Int(19))));
+        assert_eq!(t.diff.ordering, Ordering::Greater);
+        assert_eq!((t.compared, t.cases), (2, 4));
+        assert_eq!(t.format(), "-2 (89.47%) over 2/4 cases");
+    }
+
+    #[test]
+    fn test_resolve_for_previous() {
+        let out_dir = std::env::temp_dir().join(format!("heu-baseline-test-{}", std::process::id()));
+        let out_dir = out_dir.to_str().unwrap();
+        let config = crate::Config::default_config();
+        let mut records = Vec::new();
+        for id in ["20240101-000000-2", "20240101-000000-10", "20240101-000000"] {
+            let mut r = RunRecord::new(&config, Vec::new());
+            r.id = id.to_string();
+            history::save(out_dir, &mut r).unwrap();
+            records.push(r);
+        }
+        let prev = |r: &RunRecord| Baseline::resolve_for(out_dir, "previous", r, Objective::Max).map(|b| b.id);
+        assert_eq!(prev(&records[1]).unwrap(), "20240101-000000-2");
+        assert_eq!(prev(&records[0]).unwrap(), "20240101-000000");
+        assert!(prev(&records[2]).is_err());
+        std::fs::remove_dir_all(out_dir).unwrap();
+    }
+
+    #[test]
+    fn test_relative_diff() {
+        let diff = |cur: Option<i64>, base: Option<i64>, objective| {
+            let d = CaseDiff::new(cur.map(Score::Int), base.map(Score::Int), objective).relative_diff(objective);
+            d.map(|d| (d * 1e6).round() / 1e6)
+        };
+        assert_eq!(diff(Some(1000), Some(800), Objective::Max), Some(0.2));
+        assert_eq!(diff(Some(800), Some(1000), Objective::Max), Some(-0.2));
+        assert_eq!(diff(Some(1000), Some(800), Objective::Min), Some(-0.2));
+        assert_eq!(diff(None, Some(5), Objective::Max), Some(-1.0));
+        assert_eq!(diff(None, None, Objective::Max), None);
+    }
+}
@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::{CaseResult, Objective, Score};
+
+/// 相対スコアの満点 (AHC と同じく 1 ケースあたり 10^9)。
+pub const RELATIVE_SCALE: f64 = 1e9;
+
+/// ケースごとの既知の最良スコア。`{out_dir}/best.json` に保存される。
+#[derive(Default, Serialize, Deserialize)]
+pub struct BestScores {
+    pub cases: BTreeMap<u32, BestEntry>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BestEntry {
+    pub score: Score,
+    /// このスコアを出した実行 ID。
+    pub run_id: Option<String>,
+}
+
+impl BestScores {
+    pub fn path(out_dir: &str) -> PathBuf {
+        Path::new(out_dir).join("best.json")
+    }
+
+    /// 保存されている最良スコアを読み込む。ファイルが無ければ空。
+    pub fn load(out_dir: &str) -> io::Result<Self> {
+        let path = Self::path(out_dir);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(&path)?;
+        serde_json::from_str(&content).map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e))
+        })
+    }
+
+    pub fn save(&self, out_dir: &str) -> io::Result<()> {
+        fs::create_dir_all(out_dir)?;
+        let json = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(Self::path(out_dir), json)
+    }
+
+    pub fn get(&self, case: u32) -> Option<Score> {
+        self.cases.get(&case).map(|e| e.score)
+    }
+
+    /// 成功したケースのスコアで最良スコアを更新し、更新したケース数を返す。
+    pub fn update(&mut self, results: &[CaseResult], run_id: Option<&str>, objective: Objective) -> usize {
+        let mut updated = 0;
+        for r in results {
+            let Some(score) = r.ok_score() else {
+                continue;
+            };
+            let better = match self.get(r.case) {
+                Some(best) => objective.is_better(score, best),
+                None => true,
+            };
+            if better {
+                self.cases.insert(r.case, BestEntry { score, run_id: run_id.map(String::from) });
+                updated += 1;
+            }
+        }
+        updated
+    }
+
+    /// ケースの相対スコア。今回のスコアも最良スコアの候補に含めて計算する。
+    /// 失敗したケースは 0。
+    pub fn relative(&self, result: &CaseResult, objective: Objective) -> u64 {
+        let Some(score) = result.ok_score() else {
+            return 0;
+        };
+        let best = match self.get(result.case) {
+            Some(b) if objective.is_better(b, score) => b,
+            _ => score,
+        };
+        relative_score(score, best, objective)
+    }
+}
+
+/// AHC の相対スコア。最大化なら round(10^9 * yours / best)、最小化なら round(10^9 * best / yours)。
+/// 比が定義できない (分母が 0 以下) 場合は、最大化・最小化のどちらでも最良スコアと同じなら満点、
+/// それ以外は 0 とする。
+pub fn relative_score(score: Score, best: Score, objective: Objective) -> u64 {
+    let (score, best) = (score.as_f64(), best.as_f64());
+    let ratio = match objective {
+        Objective::Max if best > 0.0 => score / best,
+        Objective::Min if score > 0.0 => best / score,
+        _ => return if score == best { RELATIVE_SCALE as u64 } else { 0 },
+    };
+    (RELATIVE_SCALE * ratio.max(0.0)).round() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relative_score() {
+        let rel = |s: i64, b: i64, o| relative_score(Score::Int(s), Score::Int(b), o);
+        assert_eq!(rel(50, 100, Objective::Max), 500_000_000);
+        assert_eq!(rel(100, 100, Objective::Max), 1_000_000_000);
+        assert_eq!(rel(200, 100, Objective::Min), 500_000_000);
+        assert_eq!(rel(0, 0, Objective::Max), 1_000_000_000);
+        assert_eq!(rel(0, 0, Objective::Min), 1_000_000_000);
+        assert_eq!(rel(-3, 0, Objective::Max), 0);
+        assert_eq!(rel(3, 0, Objective::Min), 0);
+        assert_eq!(rel(-5, 10, Objective::Max), 0);
+        assert_eq!(relative_score(Score::Float(0.5), Score::Float(0.25), Objective::Min), 500_000_000);
+    }
+
+    #[test]
+    fn test_update_keeps_best() {
+        let mut best = BestScores::default();
+        let mut r = CaseResult::ok_for_test(0, 100);
+        assert_eq!(best.update(std::slice::from_ref(&r), Some("a"), Objective::Min), 1);
+        r.score = Some(Score::Int(120));
+        assert_eq!(best.update(std::slice::from_ref(&r), Some("b"), Objective::Min), 0);
+        assert_eq!(best.relative(&r, Objective::Min), relative_score(Score::Int(120), Score::Int(100), Objective::Min));
+        r.score = Some(Score::Int(80));
+        assert_eq!(best.relative(&r, Objective::Min), 1_000_000_000);
+        assert_eq!(best.update(std::slice::from_ref(&r), Some("c"), Objective::Min), 1);
+        assert_eq!(best.cases[&0].run_id.as_deref(), Some("c"));
+    }
+}
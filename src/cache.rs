@@ -0,0 +1,202 @@
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::hash::{self, Fnv64};
+use crate::process::ProcessOutput;
+use crate::{CaseFiles, CaseResult, Heu};
+
+/// キャッシュの保存先。ケースごとに `<key>.json` (結果)、`<key>.out` (出力)、`<key>.err` (stderr) を置く。
+pub fn cache_dir(out_dir: &str) -> PathBuf {
+    PathBuf::from(out_dir).join("cache")
+}
+
+impl Heu {
+    /// `test.cache` が有効なら、1ケースの結果を決めるもののハッシュを返す。
+    /// 実行バイナリ (use_tester なら tester も) とビジュアライザ、入力、渡す環境変数、
+    /// `test.cache_env` の環境変数の今の値、評価の設定から計算する。
+    /// 評価しない場合とハッシュを計算できない場合は None (キャッシュを使わない)。
+    pub(crate) fn cache_key(&self, input: &[u8], env: &[(String, String)]) -> Option<String> {
+        self.cache_key_with(input, env, |name| std::env::var_os(name))
+    }
+
+    /// [`Self::cache_key`] の本体。`test.cache_env` の環境変数の値は `var` で引く。
+    fn cache_key_with(
+        &self,
+        input: &[u8],
+        env: &[(String, String)],
+        var: impl Fn(&str) -> Option<OsString>,
+    ) -> Option<String> {
+        let test = &self.config.test;
+        if !test.cache || test.no_evaluate {
+            return None;
+        }
+        let mut h = Fnv64::default();
+        h.field(self.cached_command_hash()?.as_bytes());
+        h.field(input);
+        let mut env: Vec<&(String, String)> = env.iter().collect();
+        env.sort();
+        for (k, v) in env {
+            h.field(k.as_bytes()).field(v.as_bytes());
+        }
+        let mut names: Vec<&String> = test.cache_env.iter().collect();
+        names.sort();
+        names.dedup();
+        for name in names {
+            // 未設定と空文字列を区別する
+            let value = var(name);
+            h.field(name.as_bytes()).field(&[value.is_some() as u8]);
+            h.field(value.unwrap_or_default().as_encoded_bytes());
+        }
+        h.field(test.score_regex.as_bytes())
+            .field(test.score_type.to_string().as_bytes())
+            .field(format!("{:?}", test.time_limit).as_bytes());
+        Some(h.hex())
+    }
+
+    /// 実行バイナリ (use_tester なら tester も) とビジュアライザのハッシュ ([`hash::executable_hash`])。
+    /// ビルドしてから最初に呼んだときに計算する。計算できなければ警告を出し、次のビルドまでキャッシュを使わない。
+    fn cached_command_hash(&self) -> Option<String> {
+        let test = &self.config.test;
+        let mut memo = self.command_hash.lock().unwrap();
+        if memo.is_none() {
+            let mut commands = vec![&test.bin];
+            if test.use_tester {
+                commands.push(&test.tester);
+            }
+            commands.push(&test.vis);
+            let mut h = Fnv64::default();
+            let hashed = commands.iter().try_for_each(|cmd| {
+                h.field(hash::executable_hash(cmd)?.as_bytes());
+                Ok::<_, io::Error>(())
+            });
+            *memo = Some(match hashed {
+                Ok(()) => Some(h.hex()),
+                Err(e) => {
+                    eprintln!("Warning: cache disabled: {}", e);
+                    None
+                }
+            });
+        }
+        memo.clone().flatten()
+    }
+
+    /// キャッシュした結果があれば、出力と stderr をケースのファイルに書き戻して返す。
+    /// 読めない場合はキャッシュが無いものとして扱う。
+    pub(crate) fn load_cached(&self, key: &str, case: u32, files: &CaseFiles) -> Option<CaseResult> {
+        let dir = cache_dir(&self.config.test.out_dir);
+        let json = fs::read_to_string(dir.join(format!("{}.json", key))).ok()?;
+        let mut result: CaseResult = serde_json::from_str(&json).ok()?;
+        fs::copy(dir.join(format!("{}.out", key)), &files.outf).ok()?;
+        fs::copy(dir.join(format!("{}.err", key)), &files.errf).ok()?;
+        result.stderr = String::from_utf8_lossy(&fs::read(&files.errf).ok()?).to_string();
+        // コメントは今の comment_regex で取り直す
+        result.comments = CaseResult::lookup_comments_from(&result.stderr, &self.comment_regex);
+        result.case = case;
+        result.inf = files.inf.clone();
+        result.outf = files.outf.clone();
+        result.errf = files.errf.clone();
+        result.cached = true;
+        Some(result)
+    }
+
+    /// 結果と出力を保存する。結果のファイルを最後に置くので、途中で止まっても壊れたキャッシュは使われない。
+    pub(crate) fn store_cached(&self, key: &str, result: &CaseResult, run: &ProcessOutput) -> io::Result<()> {
+        let dir = cache_dir(&self.config.test.out_dir);
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join(format!("{}.out", key)), &run.stdout)?;
+        fs::write(dir.join(format!("{}.err", key)), &run.stderr)?;
+        let tmp = dir.join(format!("{}.json.tmp", key));
+        fs::write(&tmp, serde_json::to_string(result).map_err(io::Error::other)?)?;
+        fs::rename(tmp, dir.join(format!("{}.json", key)))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use crate::{Config, Heu, Score};
+    use std::fs;
+
+    #[test]
+    fn test_cache() {
+        let dir = std::env::temp_dir().join(format!("heu-cache-test-{}", std::process::id()));
+        let in_dir = dir.join("in");
+        fs::create_dir_all(&in_dir).unwrap();
+        fs::write(in_dir.join("0000.txt"), "1 2 3\n").unwrap();
+        let mut config = Config::default_config();
+        config.test.bin = "cat".to_string();
+        let vis = dir.join("vis.sh");
+        fs::write(&vis, "echo Score = 7").unwrap();
+        config.test.vis = format!("sh {}", vis.display());
+        config.test.in_dir = in_dir.display().to_string();
+        config.test.out_dir = dir.join("out").display().to_string();
+        config.test.cases = "0".to_string();
+        config.test.cache = true;
+        config.test.cache_env = vec!["HEU_CACHE_TEST_MUL".to_string()];
+        let heu = Heu::new(config);
+
+        let env = vec![("X".to_string(), "1".to_string())];
+        let first = heu.execute_case(0, &env);
+        assert!(!first.cached);
+        assert_eq!(first.ok_score(), Some(Score::Int(7)));
+        fs::remove_file(&first.outf).unwrap();
+        let second = heu.execute_case(0, &env);
+        assert!(second.cached);
+        assert_eq!(second.ok_score(), Some(Score::Int(7)));
+        assert_eq!(fs::read_to_string(&second.outf).unwrap(), "1 2 3\n");
+
+        // 環境変数や入力が変われば実行し直す
+        assert!(!heu.execute_case(0, &[("X".to_string(), "2".to_string())]).cached);
+        fs::write(in_dir.join("0000.txt"), "4 5 6\n").unwrap();
+        assert!(!heu.execute_case(0, &env).cached);
+        assert!(heu.execute_case(0, &env).cached);
+        // test.cache_env の環境変数が変わればキーも変わる
+        let unset = heu.cache_key_with(b"", &env, |_| None);
+        assert_ne!(heu.cache_key_with(b"", &env, |_| Some("2".into())), unset);
+        assert_ne!(heu.cache_key_with(b"", &env, |_| Some("".into())), unset);
+        // ビジュアライザを作り直しても実行し直す
+        fs::write(&vis, "echo Score = 8").unwrap();
+        *heu.command_hash.lock().unwrap() = None;
+        let rerun = heu.execute_case(0, &env);
+        assert!(!rerun.cached);
+        assert_eq!(rerun.ok_score(), Some(Score::Int(8)));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_cache_cargo_run_vis() {
+        // 既定の設定と同じ `cargo run` のビジュアライザでも、ソースを変えればキャッシュを使わない
+        let dir = std::env::temp_dir().join(format!("heu-cache-cargo-test-{}", std::process::id()));
+        let tools = dir.join("tools");
+        fs::create_dir_all(tools.join("src/bin")).unwrap();
+        fs::write(tools.join("Cargo.toml"), "[package]\nname = \"tools\"\nversion = \"0.1.0\"\nedition = \"2021\"\n[workspace]\n")
+            .unwrap();
+        let vis_src = tools.join("src/bin/vis.rs");
+        fs::write(&vis_src, "fn main() { println!(\"Score = 7\"); }").unwrap();
+        let in_dir = dir.join("in");
+        fs::create_dir_all(&in_dir).unwrap();
+        fs::write(in_dir.join("0000.txt"), "1\n").unwrap();
+        let mut config = Config::default_config();
+        config.test.bin = "cat".to_string();
+        config.test.vis = format!(
+            "cargo run --manifest-path {} --bin vis --target-dir={} -r",
+            tools.join("Cargo.toml").display(),
+            tools.join("target").display()
+        );
+        config.test.in_dir = in_dir.display().to_string();
+        config.test.out_dir = dir.join("out").display().to_string();
+        config.test.cases = "0".to_string();
+        config.test.cache = true;
+        let heu = Heu::new(config);
+
+        assert_eq!(heu.execute_case(0, &[]).ok_score(), Some(Score::Int(7)));
+        assert!(heu.execute_case(0, &[]).cached);
+        fs::write(&vis_src, "fn main() { println!(\"Score = 8\"); }").unwrap();
+        *heu.command_hash.lock().unwrap() = None;
+        let rerun = heu.execute_case(0, &[]);
+        assert!(!rerun.cached);
+        assert_eq!(rerun.ok_score(), Some(Score::Int(8)));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
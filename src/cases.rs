@@ -0,0 +1,298 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::collections::HashSet;
+use std::io;
+use std::path::Path;
+
+use crate::baseline::Baseline;
+use crate::best::relative_score;
+use crate::{history, Config};
+
+/// 名前付き集合の参照の深さの上限 (循環参照の検出用)。
+const MAX_SET_DEPTH: usize = 16;
+
+/// ケース指定の1トークン。
+#[derive(Debug, Clone, PartialEq)]
+pub enum Selector {
+    /// "A", "A-B", "A-B:STEP"。
+    Range { start: u32, end: u32, step: u32 },
+    /// "@name": 設定の `[sets]` に定義した集合。
+    Set(String),
+    /// "failed" / "failed:ID": 直前 (または指定した実行) で失敗したケース。
+    Failed(Option<String>),
+    /// "worst:N": 直前の実行でベースラインに対して最も悪かった N ケース。
+    Worst(usize),
+}
+
+/// "rand:K" / "rand:K:SEED": 選んだケースからランダムに K ケースを選ぶ。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    pub count: usize,
+    pub seed: Option<u64>,
+}
+
+/// パースしたケース指定。選んだケースの和集合から除外を引き、必要ならランダムに間引く。
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CaseSpec {
+    pub include: Vec<Selector>,
+    /// "^" を付けたトークン。
+    pub exclude: Vec<Selector>,
+    pub sample: Option<Sample>,
+}
+
+fn invalid(token: &str, reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, format!("invalid case '{}': {}", token, reason))
+}
+
+impl CaseSpec {
+    /// 空白区切りのケース指定をパースする。解釈できないトークンがあればエラー。
+    pub fn parse(spec: &str) -> io::Result<Self> {
+        let mut ret = CaseSpec::default();
+        for token in spec.split_whitespace() {
+            if let Some(rest) = token.strip_prefix("rand:") {
+                if ret.sample.is_some() {
+                    return Err(invalid(token, "rand can be given only once"));
+                }
+                ret.sample = Some(Self::parse_sample(token, rest)?);
+            } else if let Some(rest) = token.strip_prefix('^') {
+                ret.exclude.push(Self::parse_selector(token, rest)?);
+            } else {
+                ret.include.push(Self::parse_selector(token, token)?);
+            }
+        }
+        Ok(ret)
+    }
+
+    fn parse_sample(token: &str, s: &str) -> io::Result<Sample> {
+        let (count, seed) = match s.split_once(':') {
+            Some((c, seed)) => (c, Some(seed.parse().map_err(|_| invalid(token, "seed must be a number"))?)),
+            None => (s, None),
+        };
+        let count = count.parse().map_err(|_| invalid(token, "expected rand:K or rand:K:SEED"))?;
+        Ok(Sample { count, seed })
+    }
+
+    fn parse_selector(token: &str, s: &str) -> io::Result<Selector> {
+        if let Some(name) = s.strip_prefix('@') {
+            if name.is_empty() {
+                return Err(invalid(token, "set name is empty"));
+            }
+            return Ok(Selector::Set(name.to_string()));
+        }
+        if s == "failed" {
+            return Ok(Selector::Failed(None));
+        }
+        if let Some(id) = s.strip_prefix("failed:") {
+            return Ok(Selector::Failed(Some(id.to_string())));
+        }
+        if let Some(n) = s.strip_prefix("worst:") {
+            let n = n.parse().map_err(|_| invalid(token, "expected worst:N"))?;
+            return Ok(Selector::Worst(n));
+        }
+
+        let num = |v: &str| {
+            v.parse::<u32>()
+                .map_err(|_| invalid(token, "expected N, A-B, A-B:STEP, ^..., rand:K, @set, failed or worst:N"))
+        };
+        let (range, step) = match s.split_once(':') {
+            Some((range, step)) => {
+                let step = num(step)?;
+                if step == 0 {
+                    return Err(invalid(token, "step must be positive"));
+                }
+                if !range.contains('-') {
+                    return Err(invalid(token, "step needs a range (A-B:STEP)"));
+                }
+                (range, step)
+            }
+            None => (s, 1),
+        };
+        let (start, end) = match range.split_once('-') {
+            Some((a, b)) => (num(a)?, num(b)?),
+            None => {
+                let n = num(range)?;
+                (n, n)
+            }
+        };
+        if start > end {
+            return Err(invalid(token, "range start is greater than end"));
+        }
+        Ok(Selector::Range { start, end, step })
+    }
+
+    /// ケース番号の列にする。重複は最初に現れた位置だけ残し、1ケースも選ばれなければエラー。
+    /// 何も選ばなければ 0-4 (rand のみの場合は入力ファイルがあるケース全体) から選ぶ。
+    /// 集合・履歴・入力ファイルを参照するトークンには `config` が必要。
+    pub fn resolve(&self, config: Option<&Config>) -> io::Result<Vec<u32>> {
+        let cases = self.resolve_with_depth(config, 0)?;
+        if cases.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "no cases selected"));
+        }
+        Ok(cases)
+    }
+
+    fn resolve_with_depth(&self, config: Option<&Config>, depth: usize) -> io::Result<Vec<u32>> {
+        let mut cases = Vec::new();
+        for sel in &self.include {
+            cases.extend(sel.resolve(config, depth)?);
+        }
+        if self.include.is_empty() {
+            cases = match self.sample {
+                Some(_) => existing_inputs(need_config(config, "rand without cases")?)?,
+                None => (0..5).collect(),
+            };
+        }
+
+        let mut excluded = HashSet::new();
+        for sel in &self.exclude {
+            excluded.extend(sel.resolve(config, depth)?);
+        }
+        let mut seen = HashSet::new();
+        cases.retain(|c| !excluded.contains(c) && seen.insert(*c));
+
+        if let Some(sample) = self.sample {
+            let seed = sample.seed.unwrap_or_else(|| {
+                let seed = rand::random::<u32>() as u64;
+                eprintln!("Sampled {} cases with seed {} (rand:{}:{} to reproduce)", sample.count, seed, sample.count, seed);
+                seed
+            });
+            let mut rng = StdRng::seed_from_u64(seed);
+            let count = sample.count.min(cases.len());
+            let mut picked = rand::seq::index::sample(&mut rng, cases.len(), count).into_vec();
+            picked.sort_unstable();
+            cases = picked.into_iter().map(|i| cases[i]).collect();
+        }
+        Ok(cases)
+    }
+}
+
+impl Selector {
+    fn resolve(&self, config: Option<&Config>, depth: usize) -> io::Result<Vec<u32>> {
+        match self {
+            Selector::Range { start, end, step } => Ok((*start..=*end).step_by(*step as usize).collect()),
+            Selector::Set(name) => {
+                let config = need_config(config, &format!("@{}", name))?;
+                let spec = config.sets.get(name).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, format!("unknown case set '@{}'", name))
+                })?;
+                if depth >= MAX_SET_DEPTH {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("case set '@{}' is nested too deeply (circular reference?)", name),
+                    ));
+                }
+                let parsed = CaseSpec::parse(spec)
+                    .map_err(|e| io::Error::new(e.kind(), format!("in set '@{}': {}", name, e)))?;
+                parsed.resolve_with_depth(Some(config), depth + 1)
+            }
+            Selector::Failed(id) => {
+                let config = need_config(config, "failed")?;
+                let record = match id {
+                    Some(id) => history::load(&config.test.out_dir, id)?,
+                    None => history::load_all(&config.test.out_dir)?
+                        .pop()
+                        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no runs in history"))?,
+                };
+                Ok(record.cases.iter().filter(|r| !r.verdict.is_ok()).map(|r| r.case).collect())
+            }
+            Selector::Worst(n) => worst_cases(need_config(config, "worst")?, *n),
+        }
+    }
+}
+
+fn need_config<'a>(config: Option<&'a Config>, what: &str) -> io::Result<&'a Config> {
+    config.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("'{}' needs a config", what)))
+}
+
+/// `in_dir` にある "NNNN.txt" のケース番号を昇順で返す。
+fn existing_inputs(config: &Config) -> io::Result<Vec<u32>> {
+    let dir = Path::new(&config.test.in_dir);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut cases: Vec<u32> = std::fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let name = e.file_name();
+            let stem = name.to_str()?.strip_suffix(".txt")?;
+            stem.parse().ok()
+        })
+        .collect();
+    cases.sort_unstable();
+    Ok(cases)
+}
+
+/// 直前の実行のうち、ベースライン (`test.baseline`) に対する相対スコアが低い N ケース。
+/// 失敗したケースが最も悪く、ベースラインで失敗していたケースや無いケースは対象外。
+/// ベースラインが "previous" の場合は直前の実行のさらに1つ前と比べる。
+fn worst_cases(config: &Config, n: usize) -> io::Result<Vec<u32>> {
+    let out_dir = &config.test.out_dir;
+    let objective = config.test.objective;
+    let spec = config.test.baseline.as_deref().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, "worst:N needs a baseline (test.baseline or --baseline)")
+    })?;
+    let last = history::load_all(out_dir)?
+        .pop()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no runs in history"))?;
+    let baseline = Baseline::resolve_for(out_dir, spec, &last, objective)?;
+
+    let mut ranked: Vec<(u64, u32)> = last
+        .cases
+        .iter()
+        .filter_map(|r| {
+            let diff = baseline.diff(r)?;
+            let base = diff.base?;
+            Some((diff.current.map_or(0, |c| relative_score(c, base, objective)), r.case))
+        })
+        .collect();
+    ranked.sort_unstable();
+    Ok(ranked.into_iter().take(n).map(|(_, c)| c).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(spec: &str) -> io::Result<Vec<u32>> {
+        CaseSpec::parse(spec)?.resolve(None)
+    }
+
+    #[test]
+    fn test_ranges_steps_and_exclusions() {
+        assert_eq!(resolve("").unwrap(), vec![0, 1, 2, 3, 4]);
+        assert_eq!(resolve("0-10:5").unwrap(), vec![0, 5, 10]);
+        assert_eq!(resolve("0-4 ^1-2").unwrap(), vec![0, 3, 4]);
+        assert_eq!(resolve("^3").unwrap(), vec![0, 1, 2, 4]);
+        assert_eq!(resolve("5 0-6:3 5 3").unwrap(), vec![5, 0, 3, 6]);
+    }
+
+    #[test]
+    fn test_malformed_tokens() {
+        for spec in ["3-", "a", "5-3", "-1", "0-9:0", "3:2", "rand:x", "rand:1 rand:2", "worst:", "@", "1--2"] {
+            assert!(resolve(spec).is_err(), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn test_rand_is_reproducible() {
+        let a = resolve("0-99 rand:10:42").unwrap();
+        assert_eq!(a.len(), 10);
+        assert_eq!(a, resolve("0-99 rand:10:42").unwrap());
+        assert!(a.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(resolve("0-2 rand:10:1").unwrap(), vec![0, 1, 2]);
+        assert!(resolve("0-9 rand:0").is_err());
+    }
+
+    #[test]
+    fn test_sets() {
+        let mut config = Config::default_config();
+        config.sets.insert("small".to_string(), "0-9 ^5".to_string());
+        config.sets.insert("mix".to_string(), "@small ^0-7 20".to_string());
+        config.sets.insert("loop".to_string(), "@loop".to_string());
+        let resolve = |spec: &str| CaseSpec::parse(spec).unwrap().resolve(Some(&config));
+        assert_eq!(resolve("@mix").unwrap(), vec![8, 9, 20]);
+        assert!(resolve("@nope").is_err());
+        assert!(resolve("@loop").is_err());
+        assert!(CaseSpec::parse("@small").unwrap().resolve(None).is_err());
+    }
+}
@@ -0,0 +1,265 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::Path;
+
+use crate::baseline::{self, CaseDiff, TotalDiff, WinLoss};
+use crate::best::BestScores;
+use crate::stats::{wilcoxon_signed_rank, Significance, Wilcoxon, SIGNIFICANCE_LEVEL};
+use crate::sweep::{ParamValue, Params};
+use crate::{
+    format_stat, format_with_commas, paint, run_ordered, CaseResult, Config, Heu, Objective, Summary, GREEN, RED,
+};
+
+/// `[profiles.<name>]`: `cargo heu compare` で比べる実行方法。省略したキーは `[test]` の設定を使う。
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    /// 実行するコマンド。省略時は `test.bin`。
+    pub bin: Option<String>,
+    /// 実行前に追加で行うビルドコマンド。
+    pub build: Option<String>,
+    /// ソリューションに渡す環境変数。
+    #[serde(default)]
+    pub env: BTreeMap<String, ParamValue>,
+}
+
+/// 比較する一方。
+#[derive(Debug, Clone, PartialEq)]
+pub struct Side {
+    pub label: String,
+    pub profile: Profile,
+}
+
+impl Side {
+    /// `--bin-X` または `--profile-X` から作る。どちらも無ければ設定のまま (`test.bin`)。
+    pub fn resolve(config: &Config, bin: Option<&str>, profile: Option<&str>) -> io::Result<Self> {
+        match (bin, profile) {
+            (Some(_), Some(_)) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "give either a binary or a profile for each side, not both",
+            )),
+            (Some(bin), None) => Ok(Side {
+                label: bin.to_string(),
+                profile: Profile { bin: Some(bin.to_string()), ..Profile::default() },
+            }),
+            (None, Some(name)) => {
+                let profile = config.profiles.get(name).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, format!("unknown profile '{}'", name))
+                })?;
+                Ok(Side { label: name.to_string(), profile: profile.clone() })
+            }
+            (None, None) => Ok(Side { label: config.test.bin.clone(), profile: Profile::default() }),
+        }
+    }
+
+    fn env(&self) -> Params {
+        self.profile.env.iter().map(|(k, v)| (k.clone(), v.to_string())).collect()
+    }
+}
+
+/// 同じケースを A と B で実行した結果。
+pub struct CasePair {
+    pub a: CaseResult,
+    pub b: CaseResult,
+}
+
+/// 2つの実行方法の比較結果。B を A と比べる。
+pub struct Comparison {
+    pub pairs: Vec<CasePair>,
+    pub a: Summary,
+    pub b: Summary,
+    /// B から見た勝ち負け。
+    pub win_loss: WinLoss,
+    /// ケースごとの相対スコアの差 (B - A)。両方失敗したケースは含めない。
+    pub diffs: Vec<f64>,
+    pub wilcoxon: Option<Wilcoxon>,
+    /// 相対スコアの差の平均と信頼区間。
+    pub significance: Option<Significance>,
+}
+
+impl Comparison {
+    /// 両方で成功したケースだけの B と A のスコア合計の比較。
+    pub fn total_diff(&self, objective: Objective) -> TotalDiff {
+        let diffs: Vec<CaseDiff> =
+            self.pairs.iter().map(|p| CaseDiff::new(p.b.ok_score(), p.a.ok_score(), objective)).collect();
+        TotalDiff::new(&diffs, objective)
+    }
+}
+
+impl Heu {
+    /// `side` の実行方法で動く Heu。出力は `{out_dir}/compare/{dir}` に書く。
+    fn for_side(&self, side: &Side, dir: &str) -> Heu {
+        let mut config = self.config.clone();
+        if let Some(bin) = &side.profile.bin {
+            config.test.bin = bin.clone();
+        }
+        config.test.out_dir = Path::new(&self.config.test.out_dir).join("compare").join(dir).to_string_lossy().into_owned();
+        self.with_config_and_cases(config, self.cases.clone())
+    }
+
+    /// 同じケースを A と B の両方で実行し、ケースごとの勝敗、合計の差、
+    /// 相対スコアの差の信頼区間と Wilcoxon の符号付き順位検定を表示する。
+    /// ケースごとに A と B を続けて実行し、先に実行する方を交互に入れ替えて負荷の偏りを打ち消す。
+    /// 実行履歴や最良スコアは更新しない。
+    pub fn compare(&self, a: &Side, b: &Side) -> io::Result<Comparison> {
+        self.prepare()?;
+        for side in [a, b] {
+            if let Some(cmd) = &side.profile.build {
+                eprintln!("Building {}: {}", side.label, cmd);
+                if !self.run_build(cmd)? {
+                    return Err(io::Error::other(format!("build failed: {}", side.label)));
+                }
+            }
+        }
+        let heus = [self.for_side(a, "a"), self.for_side(b, "b")];
+        for heu in &heus {
+            fs::create_dir_all(&heu.config.test.out_dir)?;
+        }
+        let envs = [a.env(), b.env()];
+
+        let jobs: Vec<(u32, usize)> = self
+            .cases
+            .iter()
+            .enumerate()
+            .flat_map(|(i, &case)| if i % 2 == 0 { [(case, 0), (case, 1)] } else { [(case, 1), (case, 0)] })
+            .collect();
+        let objective = self.config.test.objective;
+        let color = io::stdout().is_terminal();
+        let mut pairs = Vec::with_capacity(self.cases.len());
+        let mut win_loss = WinLoss::default();
+        let mut diffs = Vec::new();
+        // 片方だけ終わったケースの結果。中断された場合は揃わないまま残る
+        let mut pending: BTreeMap<u32, (usize, CaseResult)> = BTreeMap::new();
+        run_ordered(
+            self.config.test.threads,
+            &jobs,
+            |&(case, side)| (case, side, heus[side].execute_case(case, &envs[side])),
+            |(case, side, r)| {
+                let Some((_, first)) = pending.remove(&case) else {
+                    pending.insert(case, (side, r));
+                    return;
+                };
+                let pair = if side == 1 { CasePair { a: first, b: r } } else { CasePair { a: r, b: first } };
+                let d = CaseDiff::new(pair.b.ok_score(), pair.a.ok_score(), objective);
+                win_loss.add(&d);
+                diffs.extend(d.relative_diff(objective));
+                print_pair(&pair, &d, color);
+                pairs.push(pair);
+            },
+        )?;
+
+        let score_type = self.config.test.score_type;
+        let none = BestScores::default();
+        let summaries = [0, 1].map(|i| {
+            let results: Vec<CaseResult> =
+                pairs.iter().map(|p| if i == 0 { p.a.clone() } else { p.b.clone() }).collect();
+            Summary::new(&results, &none, objective, score_type)
+        });
+        let [sa, sb] = summaries;
+        let wilcoxon = wilcoxon_signed_rank(&diffs);
+        let significance = Significance::from_diffs(&diffs);
+        let comparison = Comparison { pairs, a: sa, b: sb, win_loss, diffs, wilcoxon, significance };
+        print_comparison(&comparison, a, b, objective, color);
+        Ok(comparison)
+    }
+}
+
+fn score_text(r: &CaseResult) -> String {
+    match r.ok_score() {
+        Some(s) => format_with_commas(s),
+        // 詳細は長くなるので判定の種類だけ
+        None => r.verdict.to_string().split('(').next().unwrap_or_default().to_string(),
+    }
+}
+
+/// 1ケースの比較を1行で表示する。B が勝てば緑、負ければ赤。
+fn print_pair(pair: &CasePair, d: &CaseDiff, color: bool) {
+    let winner = match d.ordering {
+        Ordering::Greater => "B",
+        Ordering::Less => "A",
+        Ordering::Equal => "DRAW",
+    };
+    let line = format!(
+        "{:04} A[{:>11}] B[{:>11}] VS[{}] WINNER={}",
+        pair.a.case,
+        score_text(&pair.a),
+        score_text(&pair.b),
+        baseline::format_diff(d),
+        winner
+    );
+    match d.ordering {
+        Ordering::Greater if color => println!("{}", paint(&line, GREEN)),
+        Ordering::Less if color => println!("{}", paint(&line, RED)),
+        _ => println!("{}", line),
+    }
+}
+
+fn print_comparison(c: &Comparison, a: &Side, b: &Side, objective: Objective, color: bool) {
+    for (name, side, s) in [("A", a, &c.a), ("B", b, &c.b)] {
+        println!(
+            "{}: {} TOTAL={} AVG={} FAILED={}/{} MAX_ELAPSED={:.2}s",
+            name,
+            side.label,
+            format_with_commas(s.total),
+            format_stat(s.mean),
+            s.failed.len(),
+            s.cases,
+            s.max_elapsed
+        );
+    }
+    // 失敗したケースを 0 点として足すと最小化で失敗が多い方が勝つので、両方で成功したケースだけを比べる
+    let total = c.total_diff(objective);
+    println!(
+        "B VS A: TOTAL[{}] over {}/{} cases {} WIN={} LOSE={} DRAW={}",
+        baseline::format_diff(&total.diff),
+        total.compared,
+        total.cases,
+        c.significance.as_ref().map_or("REL_DIFF=-".to_string(), Significance::format),
+        c.win_loss.win,
+        c.win_loss.lose,
+        c.win_loss.draw
+    );
+    let verdict = match &c.wilcoxon {
+        Some(w) => {
+            let result = match (w.is_significant(), w.z > 0.0) {
+                (true, true) => "B is better",
+                (true, false) => "A is better",
+                (false, _) => "no significant difference",
+            };
+            format!("WILCOXON[n={} z={:.2} p={:.4}] {} at {}%", w.n, w.z, w.p_value, result, SIGNIFICANCE_LEVEL * 100.0)
+        }
+        None => "WILCOXON[-] no difference in any case".to_string(),
+    };
+    match &c.wilcoxon {
+        Some(w) if color && w.is_significant() => println!("{}", paint(&verdict, if w.z > 0.0 { GREEN } else { RED })),
+        _ => println!("{}", verdict),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_side() {
+        let mut config = Config::default_config();
+        config.profiles = toml::from_str(
+            r#"
+            fast = { bin = "./target/release/fast", build = "cargo build -r --bin fast", env = { T0 = 100 } }
+            "#,
+        )
+        .unwrap();
+        let side = Side::resolve(&config, None, Some("fast")).unwrap();
+        assert_eq!(side.label, "fast");
+        assert_eq!(side.profile.bin.as_deref(), Some("./target/release/fast"));
+        assert_eq!(side.env(), vec![("T0".to_string(), "100".to_string())]);
+
+        let side = Side::resolve(&config, Some("./b"), None).unwrap();
+        assert_eq!((side.label.as_str(), side.profile.bin.as_deref()), ("./b", Some("./b")));
+        assert_eq!(Side::resolve(&config, None, None).unwrap().label, config.test.bin);
+        assert!(Side::resolve(&config, None, Some("nope")).is_err());
+        assert!(Side::resolve(&config, Some("./b"), Some("fast")).is_err());
+    }
+}
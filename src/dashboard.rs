@@ -0,0 +1,249 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::best::{BestScores, RELATIVE_SCALE};
+use crate::{format_with_commas, CaseResult, Objective, Score, ScoreType};
+
+/// ダッシュボードを描き直す間隔。
+const REFRESH_INTERVAL: Duration = Duration::from_millis(200);
+/// 進捗バーの幅。
+const BAR_WIDTH: usize = 30;
+/// 表示する悪いケースの数。
+const WORST_CASES: usize = 5;
+/// 表示する実行中のケースの数。
+const RUNNING_CASES: usize = 8;
+
+/// 実行中に端末の下部に表示する進捗。ケースごとの行はその上に順に流れる。
+pub struct Dashboard<'a> {
+    cases: usize,
+    threads: usize,
+    objective: Objective,
+    best: &'a BestScores,
+    state: Mutex<State>,
+}
+
+struct State {
+    start: Instant,
+    /// 実行中のケースと開始時刻。
+    running: BTreeMap<u32, Instant>,
+    done: usize,
+    failed: usize,
+    total: Score,
+    elapsed_sum: f64,
+    /// 相対スコアが低い順のケース。失敗したケースは相対スコア 0。
+    worst: Vec<(u64, u32)>,
+    /// 最後に描いたダッシュボードの行数。
+    drawn: usize,
+    closed: bool,
+}
+
+impl<'a> Dashboard<'a> {
+    pub fn new(cases: usize, threads: usize, score_type: ScoreType, objective: Objective, best: &'a BestScores) -> Self {
+        let state = State {
+            start: Instant::now(),
+            running: BTreeMap::new(),
+            done: 0,
+            failed: 0,
+            total: Score::zero(score_type),
+            elapsed_sum: 0.0,
+            worst: Vec::new(),
+            drawn: 0,
+            closed: false,
+        };
+        Self { cases, threads: threads.max(1), objective, best, state: Mutex::new(state) }
+    }
+
+    /// ケースの実行を始めた。
+    pub fn start(&self, case: u32) {
+        self.state.lock().unwrap().running.insert(case, Instant::now());
+    }
+
+    /// ケースの実行が終わった (ケース番号の順とは限らない)。
+    pub fn finish(&self, r: &CaseResult) {
+        let relative = self.best.relative(r, self.objective);
+        let mut state = self.state.lock().unwrap();
+        state.running.remove(&r.case);
+        state.done += 1;
+        state.elapsed_sum += r.elapsed;
+        match r.ok_score() {
+            Some(score) => state.total = state.total + score,
+            None => state.failed += 1,
+        }
+        state.worst.push((relative, r.case));
+        state.worst.sort();
+        state.worst.truncate(WORST_CASES);
+    }
+
+    /// ダッシュボードを消して `line` を出力し、ダッシュボードを描き直す。
+    pub fn println(&self, line: &str) {
+        let mut state = self.state.lock().unwrap();
+        let mut out = io::stdout().lock();
+        let _ = writeln!(out, "{}{}", clear_lines(state.drawn), line);
+        state.drawn = 0;
+        self.draw(&mut state, &mut out);
+    }
+
+    /// `close` されるまで一定間隔で描き直す。別スレッドで呼ぶ。
+    pub fn run(&self) {
+        loop {
+            thread::sleep(REFRESH_INTERVAL);
+            let mut state = self.state.lock().unwrap();
+            if state.closed {
+                return;
+            }
+            let mut out = io::stdout().lock();
+            self.draw(&mut state, &mut out);
+        }
+    }
+
+    /// ダッシュボードを消し、描き直しを止める。
+    pub fn close(&self) {
+        let mut state = self.state.lock().unwrap();
+        let mut out = io::stdout().lock();
+        let _ = write!(out, "{}", clear_lines(state.drawn));
+        let _ = out.flush();
+        state.drawn = 0;
+        state.closed = true;
+    }
+
+    fn draw(&self, state: &mut State, out: &mut impl Write) {
+        if state.closed {
+            return;
+        }
+        let width = terminal_width();
+        let lines = self.lines(state, Instant::now());
+        let mut text = clear_lines(state.drawn);
+        for line in &lines {
+            // 折り返すと消す行数がずれるので端末の幅で切る
+            text.extend(line.chars().take(width.saturating_sub(1)));
+            text.push('\n');
+        }
+        let _ = out.write_all(text.as_bytes());
+        let _ = out.flush();
+        state.drawn = lines.len();
+    }
+
+    /// 残りの時間の見積もり (秒)。1ケースも終わっていなければ None。
+    /// 終わったケースの平均実行時間で、まだ始まっていないケースと実行中のケースの残りを並列数で割る。
+    fn eta(&self, state: &State, now: Instant) -> Option<f64> {
+        if state.done == 0 {
+            return None;
+        }
+        let mean = state.elapsed_sum / state.done as f64;
+        let waiting = self.cases.saturating_sub(state.done + state.running.len());
+        let running: f64 = state.running.values().map(|t| (mean - (now - *t).as_secs_f64()).max(0.0)).sum();
+        let threads = self.threads.min(self.cases - state.done).max(1);
+        Some((waiting as f64 * mean + running) / threads as f64)
+    }
+
+    fn lines(&self, state: &State, now: Instant) -> Vec<String> {
+        let ratio = if self.cases == 0 { 1.0 } else { state.done as f64 / self.cases as f64 };
+        let filled = ((ratio * BAR_WIDTH as f64) as usize).min(BAR_WIDTH);
+        let mut lines = vec![format!(
+            "[{}{}] {}/{} ({:.1}%) ELAPSED {} ETA {}",
+            "#".repeat(filled),
+            "-".repeat(BAR_WIDTH - filled),
+            state.done,
+            self.cases,
+            ratio * 100.0,
+            format_duration((now - state.start).as_secs_f64()),
+            self.eta(state, now).map_or("-".to_string(), format_duration)
+        )];
+        let ok = state.done - state.failed;
+        let avg = if ok > 0 { format_with_commas(format!("{:.2}", state.total.as_f64() / ok as f64)) } else { "-".to_string() };
+        lines.push(format!("TOTAL={} AVG={} FAILED={}", format_with_commas(state.total), avg, state.failed));
+
+        let mut running: Vec<(f64, u32)> =
+            state.running.iter().map(|(&case, t)| ((now - *t).as_secs_f64(), case)).collect();
+        running.sort_by(|a, b| b.0.total_cmp(&a.0));
+        let items: Vec<String> =
+            running.iter().take(RUNNING_CASES).map(|(t, case)| format!("{:04}({:.1}s)", case, t)).collect();
+        lines.push(format!("RUNNING[{}]: {}", running.len(), items.join(" ")));
+
+        let items: Vec<String> = state
+            .worst
+            .iter()
+            .map(|&(rel, case)| match rel {
+                0 => format!("{:04}(FAILED)", case),
+                rel => format!("{:04}({:.2}%)", case, rel as f64 / RELATIVE_SCALE * 100.0),
+            })
+            .collect();
+        lines.push(format!("WORST: {}", items.join(" ")));
+        lines
+    }
+}
+
+/// 直前に描いた `n` 行を消すエスケープシーケンス。
+fn clear_lines(n: usize) -> String {
+    if n == 0 {
+        String::new()
+    } else {
+        format!("\x1b[{}F\x1b[J", n)
+    }
+}
+
+/// "1:02:03" または "2:03" の形式。
+fn format_duration(secs: f64) -> String {
+    let secs = secs.round() as u64;
+    let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if h > 0 {
+        format!("{}:{:02}:{:02}", h, m, s)
+    } else {
+        format!("{}:{:02}", m, s)
+    }
+}
+
+#[cfg(unix)]
+fn terminal_width() -> usize {
+    // SAFETY: winsize は整数だけの C 構造体で、ioctl が書き込む
+    let mut ws: libc::winsize = unsafe { std::mem::zeroed() };
+    let ret = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut ws) };
+    if ret == 0 && ws.ws_col > 0 {
+        ws.ws_col as usize
+    } else {
+        80
+    }
+}
+
+#[cfg(not(unix))]
+fn terminal_width() -> usize {
+    80
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dashboard_lines() {
+        let best = BestScores::default();
+        let dash = Dashboard::new(10, 2, ScoreType::Int, Objective::Max, &best);
+        let now = Instant::now();
+        {
+            let state = dash.state.lock().unwrap();
+            assert_eq!(dash.eta(&state, now), None);
+        }
+        dash.finish(&CaseResult { elapsed: 2.0, ..CaseResult::ok_for_test(0, 100) });
+        dash.finish(&CaseResult { elapsed: 2.0, ..CaseResult::failed_for_test(1) });
+        dash.start(2);
+        let state = dash.state.lock().unwrap();
+        // 待ち 7 ケース * 2 秒 + 実行中の残り 2 秒弱を 2 並列で
+        let eta = dash.eta(&state, now).unwrap();
+        assert!((7.9..=8.0).contains(&eta), "{}", eta);
+        let lines = dash.lines(&state, now);
+        assert!(lines[0].starts_with("[######------------------------] 2/10 (20.0%)"), "{}", lines[0]);
+        assert_eq!(lines[1], "TOTAL=100 AVG=100.00 FAILED=1");
+        assert!(lines[2].starts_with("RUNNING[1]: 0002("));
+        assert_eq!(lines[3], "WORST: 0001(FAILED) 0000(100.00%)");
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(5.4), "0:05");
+        assert_eq!(format_duration(125.0), "2:05");
+        assert_eq!(format_duration(3723.0), "1:02:03");
+    }
+}
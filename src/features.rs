@@ -0,0 +1,464 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::best::relative_score;
+use crate::stats::nearest_rank;
+use crate::sweep::{align_rows, ParamValue};
+use crate::tune::{ParamSpec, Trial, TrialLog};
+use crate::{format_with_commas, hash, Heu, Objective, Score, ScoreType};
+
+/// 特徴量の名前と値。
+pub type Features = BTreeMap<String, f64>;
+
+/// 入力の1行目を空白で区切り、`names` の順に名前を付ける。"_" の位置は読み飛ばす。
+pub fn parse_header(line: &str, names: &[String]) -> Result<Features, String> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let mut ret = Features::new();
+    for (i, name) in names.iter().enumerate() {
+        if name == "_" {
+            continue;
+        }
+        let token = tokens.get(i).ok_or_else(|| format!("no value for feature '{}' (token {})", name, i + 1))?;
+        let value = token.parse().map_err(|_| format!("feature '{}' is not a number: '{}'", name, token))?;
+        ret.insert(name.clone(), value);
+    }
+    Ok(ret)
+}
+
+pub fn read_header(path: &Path, names: &[String]) -> io::Result<Features> {
+    let content = fs::read_to_string(path)?;
+    parse_header(content.lines().next().unwrap_or(""), names)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))
+}
+
+/// 特徴量ごとの区切り。区切りの値 e は e 以上の側に入る。
+#[derive(Debug, Clone, PartialEq)]
+pub struct Buckets {
+    features: Vec<(String, Vec<f64>)>,
+}
+
+/// 値の分布を `bins` 個に分ける区切り。同じ値が多ければ区間はそれより少なくなる。
+fn quantile_edges(values: &[f64], bins: usize) -> Vec<f64> {
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let mut edges: Vec<f64> = (1..bins)
+        .filter_map(|k| nearest_rank(values, k as f64 / bins as f64))
+        .filter(|&e| e > min)
+        .collect();
+    edges.dedup();
+    edges
+}
+
+impl Buckets {
+    /// `explicit` に区切りがある特徴量はそれを使い、無い特徴量は観測した値から `bins` 等分する。
+    pub fn new(names: &[String], explicit: &BTreeMap<String, Vec<f64>>, bins: usize, samples: &[Features]) -> Self {
+        let features = names
+            .iter()
+            .filter(|n| *n != "_")
+            .map(|name| {
+                let edges = match explicit.get(name) {
+                    Some(edges) => {
+                        let mut edges = edges.clone();
+                        edges.sort_by(f64::total_cmp);
+                        edges.dedup();
+                        edges
+                    }
+                    None => {
+                        let values: Vec<f64> = samples.iter().filter_map(|f| f.get(name).copied()).collect();
+                        quantile_edges(&values, bins)
+                    }
+                };
+                (name.clone(), edges)
+            })
+            .collect();
+        Self { features }
+    }
+
+    pub fn index(&self, features: &Features) -> Vec<usize> {
+        self.features
+            .iter()
+            .map(|(name, edges)| {
+                let x = features.get(name).copied().unwrap_or(f64::NAN);
+                edges.iter().take_while(|&&e| x >= e).count()
+            })
+            .collect()
+    }
+
+    /// "N<100 100<=M<200" のような区間の表記。
+    pub fn label(&self, index: &[usize]) -> String {
+        self.features
+            .iter()
+            .zip(index)
+            .filter(|((_, edges), _)| !edges.is_empty())
+            .map(|((name, edges), &i)| match (i.checked_sub(1).map(|j| edges[j]), edges.get(i)) {
+                (None, Some(hi)) => format!("{}<{}", name, hi),
+                (Some(lo), Some(hi)) => format!("{}<={}<{}", lo, name, hi),
+                (Some(lo), None) => format!("{}>={}", name, lo),
+                (None, None) => String::new(),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// `cases` を全て評価した試行のうち、`cases` での相対スコアの合計が最も高いもの。
+/// 相対スコアは候補の試行の中での各ケースの最良スコアを基準にし、失敗したケースは 0 とする。
+pub fn best_trial<'a>(trials: &'a [Trial], cases: &[u32], objective: Objective) -> Option<&'a Trial> {
+    let candidates: Vec<&Trial> =
+        trials.iter().filter(|t| cases.iter().all(|c| t.scores.contains_key(c))).collect();
+    let best_scores: Vec<Option<Score>> = cases
+        .iter()
+        .map(|c| {
+            candidates
+                .iter()
+                .filter_map(|t| t.scores[c])
+                .reduce(|a, b| if objective.is_better(b, a) { b } else { a })
+        })
+        .collect();
+    let relative = |t: &Trial| -> u64 {
+        cases
+            .iter()
+            .zip(&best_scores)
+            .map(|(c, best)| match (t.scores[c], best) {
+                (Some(s), Some(b)) => relative_score(s, *b, objective),
+                _ => 0,
+            })
+            .sum()
+    };
+    // 同点なら先に試したもの
+    candidates.into_iter().map(|t| (relative(t), t)).reduce(|a, b| if b.0 > a.0 { b } else { a }).map(|(_, t)| t)
+}
+
+/// `cases` を全て評価した試行のうち、`cargo heu tune` が best.env を選ぶのと同じ基準
+/// (`cases` で失敗したケースが少ない方、同じならスコア合計が良い方) で最も良いもの。
+/// 区間の表の `(all)` の行と区間に無い値の既定値に使い、best.env と同じパラメータになるようにする。
+pub fn overall_best_trial<'a>(
+    trials: &'a [Trial],
+    cases: &[u32],
+    objective: Objective,
+    score_type: ScoreType,
+) -> Option<&'a Trial> {
+    let restrict = |t: &Trial| Trial { scores: cases.iter().map(|&c| (c, t.scores[&c])).collect(), ..t.clone() };
+    // 同点なら先に試したもの
+    trials
+        .iter()
+        .filter(|t| cases.iter().all(|c| t.scores.contains_key(c)))
+        .map(|t| (restrict(t), t))
+        .reduce(|a, b| if b.0.compare(&a.0, objective, score_type).is_gt() { b } else { a })
+        .map(|(_, t)| t)
+}
+
+/// 1つの区間の結果。
+pub struct BucketResult {
+    pub index: Vec<usize>,
+    pub cases: Vec<u32>,
+    pub best: Option<Trial>,
+}
+
+/// Rust の識別子にする (英数字以外は "_"、小文字)。
+fn rust_ident(name: &str) -> String {
+    let ident: String = name.chars().map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' }).collect();
+    if ident.starts_with(|c: char| c.is_ascii_digit()) || ident.is_empty() {
+        format!("_{}", ident)
+    } else {
+        ident
+    }
+}
+
+fn rust_type(spec: &ParamSpec) -> &'static str {
+    match spec {
+        ParamSpec::Int { .. } => "i64",
+        ParamSpec::Float { .. } => "f64",
+        ParamSpec::Choice { values } if values.iter().all(|v| matches!(v, ParamValue::Int(_))) => "i64",
+        ParamSpec::Choice { values } if values.iter().all(|v| matches!(v, ParamValue::Int(_) | ParamValue::Float(_))) => {
+            "f64"
+        }
+        ParamSpec::Choice { values } if values.iter().all(|v| matches!(v, ParamValue::Bool(_))) => "bool",
+        ParamSpec::Choice { .. } => "&'static str",
+    }
+}
+
+fn rust_value(value: &ParamValue, ty: &str) -> String {
+    match (value, ty) {
+        (ParamValue::Int(v), "f64") => format!("{:?}", *v as f64),
+        (ParamValue::Float(v), _) => format!("{:?}", v),
+        (ParamValue::Str(s), _) => format!("{:?}", s),
+        (v, "&'static str") => format!("{:?}", v.to_string()),
+        (v, _) => v.to_string(),
+    }
+}
+
+fn rust_float(v: f64) -> String {
+    format!("{:?}", v)
+}
+
+/// 区間ごとのパラメータを返す関数を Rust のソースにする。区間に無い値や試行の無い区間は `default` を使う。
+pub fn rust_table(
+    buckets: &Buckets,
+    results: &[BucketResult],
+    default: &Trial,
+    specs: &BTreeMap<String, ParamSpec>,
+) -> String {
+    let fields: Vec<(String, &String, &'static str)> =
+        specs.iter().map(|(name, spec)| (rust_ident(name), name, rust_type(spec))).collect();
+    let init = |trial: &Trial| {
+        let values: Vec<String> = fields
+            .iter()
+            .map(|(ident, name, ty)| {
+                let v = trial.params.get(*name).map_or_else(|| "Default::default()".to_string(), |v| rust_value(v, ty));
+                format!("{}: {}", ident, v)
+            })
+            .collect();
+        format!("Params {{ {} }}", values.join(", "))
+    };
+    let key = |index: &[usize]| {
+        let items: Vec<String> = index.iter().map(|i| i.to_string()).collect();
+        if items.len() == 1 {
+            items[0].clone()
+        } else {
+            format!("({})", items.join(", "))
+        }
+    };
+
+    let mut src = String::new();
+    src.push_str("// cargo heu tune が入力の特徴量の区間ごとに選んだパラメータ。\n\n");
+    src.push_str("#[derive(Clone, Copy, Debug)]\npub struct Params {\n");
+    for (ident, _, ty) in &fields {
+        src.push_str(&format!("    pub {}: {},\n", ident, ty));
+    }
+    src.push_str("}\n\n");
+
+    let args: Vec<String> = buckets.features.iter().map(|(name, _)| format!("{}: f64", rust_ident(name))).collect();
+    let lookups: Vec<String> = buckets
+        .features
+        .iter()
+        .map(|(name, edges)| {
+            let edges: Vec<String> = edges.iter().map(|&e| rust_float(e)).collect();
+            format!("bucket({}, &[{}])", rust_ident(name), edges.join(", "))
+        })
+        .collect();
+    let lookup = if lookups.len() == 1 { lookups[0].clone() } else { format!("({})", lookups.join(", ")) };
+    src.push_str("/// 特徴量の値が属する区間で最も良かったパラメータを返す。\n");
+    src.push_str(&format!("pub fn params({}) -> Params {{\n", args.join(", ")));
+    src.push_str(&format!("    match {} {{\n", lookup));
+    for r in results {
+        if let Some(best) = &r.best {
+            src.push_str(&format!("        // {} ({} cases)\n", buckets.label(&r.index), r.cases.len()));
+            src.push_str(&format!("        {} => {},\n", key(&r.index), init(best)));
+        }
+    }
+    src.push_str("        // 全ケースで最も良かったもの\n");
+    src.push_str(&format!("        _ => {},\n", init(default)));
+    src.push_str("    }\n}\n\n");
+    src.push_str("fn bucket(x: f64, edges: &[f64]) -> usize {\n");
+    src.push_str("    edges.iter().take_while(|&&e| x >= e).count()\n}\n");
+    src
+}
+
+impl Heu {
+    /// 試行ログから、入力の特徴量 (`tune.features`) の区間ごとに最も良いパラメータを選んで表にし、
+    /// 区間からパラメータを引く Rust の関数を `tune.table` (省略時は `{out_dir}/tune/table.rs`) に書き出す。
+    /// 今の実行バイナリ (`test.bin`) で記録した試行だけを使う。
+    pub fn tune_table(&self) -> io::Result<Vec<BucketResult>> {
+        let config = &self.config.tune;
+        if config.features.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no input features: set tune.features to name the tokens of the first input line",
+            ));
+        }
+        if let Some(name) = config.buckets.keys().find(|k| !config.features.contains(k)) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("tune.buckets.{} is not in tune.features", name),
+            ));
+        }
+        let objective = self.config.test.objective;
+        let score_type = self.config.test.score_type;
+        let log_path = TrialLog::path(&self.config.test.out_dir);
+        let log = TrialLog::load(&log_path)?;
+        let bin_hash = hash::command_hash(&self.config.test.bin)?;
+        // 別のバイナリの試行と、パラメータを追加する前の試行は使わない
+        let trials: Vec<Trial> = log
+            .trials()
+            .iter()
+            .filter(|t| t.bin_hash.as_deref() == Some(bin_hash.as_str()))
+            .filter(|t| config.params.keys().all(|k| t.params.contains_key(k)))
+            .cloned()
+            .collect();
+        let default = overall_best_trial(&trials, &self.cases, objective, score_type).cloned().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "no trial of the current binary in {} covers all selected cases (run cargo heu tune first)",
+                    log_path.display()
+                ),
+            )
+        })?;
+
+        let features: Vec<Features> = self
+            .cases
+            .iter()
+            .map(|&c| read_header(Path::new(&self.input_file(c)), &config.features))
+            .collect::<io::Result<_>>()?;
+        let buckets = Buckets::new(&config.features, &config.buckets, config.bins, &features);
+        let mut groups: BTreeMap<Vec<usize>, Vec<u32>> = BTreeMap::new();
+        for (&case, f) in self.cases.iter().zip(&features) {
+            groups.entry(buckets.index(f)).or_default().push(case);
+        }
+        let results: Vec<BucketResult> = groups
+            .into_iter()
+            .map(|(index, cases)| {
+                let best = best_trial(&trials, &cases, objective).cloned();
+                BucketResult { index, cases, best }
+            })
+            .collect();
+
+        let names: Vec<&String> = config.params.keys().collect();
+        let mut rows: Vec<Vec<String>> = vec![std::iter::once("BUCKET".to_string())
+            .chain(std::iter::once("CASES".to_string()))
+            .chain(names.iter().map(|n| n.to_string()))
+            .chain(["TOTAL", "FAILED"].map(String::from))
+            .collect()];
+        let row = |label: String, cases: &[u32], trial: Option<&Trial>| {
+            let mut row = vec![label, cases.len().to_string()];
+            for name in &names {
+                row.push(trial.and_then(|t| t.params.get(*name)).map_or("-".to_string(), |v| v.to_string()));
+            }
+            match trial {
+                Some(t) => {
+                    let scores: Vec<Option<Score>> = cases.iter().map(|c| t.scores[c]).collect();
+                    let total = scores.iter().flatten().fold(Score::zero(score_type), |acc, &s| acc + s);
+                    row.push(format_with_commas(total));
+                    row.push(format!("{}/{}", scores.iter().filter(|s| s.is_none()).count(), cases.len()));
+                }
+                None => row.extend(["-".to_string(), "-".to_string()]),
+            }
+            row
+        };
+        for r in &results {
+            rows.push(row(buckets.label(&r.index), &r.cases, r.best.as_ref()));
+        }
+        rows.push(row("(all)".to_string(), &self.cases, Some(&default)));
+        for line in align_rows(&rows) {
+            println!("{}", line);
+        }
+
+        let output = config.table.as_ref().map_or_else(
+            || Path::new(&self.config.test.out_dir).join("tune").join("table.rs"),
+            Into::into,
+        );
+        if let Some(dir) = output.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&output, rust_table(&buckets, &results, &default, &config.params))?;
+        eprintln!("Wrote lookup table: {}", output.display());
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn trial(params: &[(&str, i64)], scores: &[(u32, Option<i64>)]) -> Trial {
+        Trial {
+            params: params.iter().map(|(k, v)| (k.to_string(), ParamValue::Int(*v))).collect(),
+            scores: scores.iter().map(|&(c, s)| (c, s.map(Score::Int))).collect(),
+            bin_hash: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_header() {
+        let f = parse_header("50 3 0.5", &names(&["N", "_", "P"])).unwrap();
+        assert_eq!(f, [("N".to_string(), 50.0), ("P".to_string(), 0.5)].into_iter().collect());
+        assert!(parse_header("50", &names(&["N", "M"])).is_err());
+        assert!(parse_header("x", &names(&["N"])).is_err());
+    }
+
+    #[test]
+    fn test_buckets() {
+        let samples: Vec<Features> = (1..=9).map(|n| [("N".to_string(), n as f64 * 10.0)].into_iter().collect()).collect();
+        let explicit = [("M".to_string(), vec![200.0, 100.0])].into_iter().collect();
+        let buckets = Buckets::new(&names(&["N", "M"]), &explicit, 3, &samples);
+        assert_eq!(buckets.features, vec![("N".to_string(), vec![30.0, 60.0]), ("M".to_string(), vec![100.0, 200.0])]);
+
+        let f = |n: f64, m: f64| [("N".to_string(), n), ("M".to_string(), m)].into_iter().collect::<Features>();
+        assert_eq!(buckets.index(&f(10.0, 100.0)), vec![0, 1]);
+        assert_eq!(buckets.index(&f(60.0, 999.0)), vec![2, 2]);
+        assert_eq!(buckets.label(&[0, 1]), "N<30 100<=M<200");
+        assert_eq!(buckets.label(&[2, 2]), "N>=60 M>=200");
+
+        // 全て同じ値なら区切らない
+        let same: Vec<Features> = (0..5).map(|_| [("N".to_string(), 7.0)].into_iter().collect()).collect();
+        assert!(Buckets::new(&names(&["N"]), &BTreeMap::new(), 3, &same).features[0].1.is_empty());
+    }
+
+    #[test]
+    fn test_best_trial_per_bucket() {
+        // A は小さいケースで、B は大きいケースで良い
+        let trials = vec![
+            trial(&[("T", 1)], &[(0, Some(100)), (1, Some(100)), (2, Some(50))]),
+            trial(&[("T", 2)], &[(0, Some(80)), (1, Some(90)), (2, Some(500))]),
+            trial(&[("T", 3)], &[(0, Some(999))]),
+        ];
+        let t = |cases: &[u32]| best_trial(&trials, cases, Objective::Max).map(|t| t.params["T"].clone());
+        assert_eq!(t(&[0, 1]), Some(ParamValue::Int(1)));
+        assert_eq!(t(&[0]), Some(ParamValue::Int(3)));
+        assert_eq!(t(&[2]), Some(ParamValue::Int(2)));
+        assert_eq!(t(&[0, 1, 2]), Some(ParamValue::Int(2)));
+        assert_eq!(t(&[9]), None);
+    }
+
+    #[test]
+    fn test_overall_best_trial() {
+        // 相対スコアの合計なら T=2 だが、失敗が少ない T=1 を選ぶ (cargo heu tune と同じ基準)
+        let trials = vec![
+            trial(&[("T", 1)], &[(0, Some(100)), (1, Some(100)), (2, Some(100))]),
+            trial(&[("T", 2)], &[(0, None), (1, Some(1000)), (2, Some(1000))]),
+            trial(&[("T", 3)], &[(0, Some(100)), (1, Some(100)), (2, Some(100))]),
+        ];
+        let cases = [0, 1, 2];
+        assert_eq!(best_trial(&trials, &cases, Objective::Max).unwrap().params["T"], ParamValue::Int(2));
+        let t = |cases: &[u32]| overall_best_trial(&trials, cases, Objective::Max, ScoreType::Int).map(|t| t.params["T"].clone());
+        assert_eq!(t(&cases), Some(ParamValue::Int(1)));
+        // cases の外のケースは数えない
+        assert_eq!(t(&[1, 2]), Some(ParamValue::Int(2)));
+        assert_eq!(t(&[9]), None);
+    }
+
+    #[test]
+    fn test_rust_table() {
+        let specs: BTreeMap<String, ParamSpec> = toml::from_str(
+            r#"
+            T0 = { type = "float", min = 1, max = 10 }
+            MODE = { type = "choice", values = ["a", "b"] }
+            "#,
+        )
+        .unwrap();
+        let trial = |t0: f64, mode: &str| Trial {
+            params: [("T0".to_string(), ParamValue::Float(t0)), ("MODE".to_string(), ParamValue::Str(mode.to_string()))]
+                .into_iter()
+                .collect(),
+            scores: BTreeMap::new(),
+            bin_hash: None,
+        };
+        let buckets = Buckets { features: vec![("N".to_string(), vec![100.0])] };
+        let results = vec![
+            BucketResult { index: vec![0], cases: vec![0, 1], best: Some(trial(2.0, "a")) },
+            BucketResult { index: vec![1], cases: vec![2], best: None },
+        ];
+        let src = rust_table(&buckets, &results, &trial(3.5, "b"), &specs);
+        assert!(src.contains("pub struct Params {\n    pub mode: &'static str,\n    pub t0: f64,\n}"), "{}", src);
+        assert!(src.contains("pub fn params(n: f64) -> Params {\n    match bucket(n, &[100.0]) {"), "{}", src);
+        assert!(src.contains("        // N<100 (2 cases)\n        0 => Params { mode: \"a\", t0: 2.0 },"), "{}", src);
+        assert!(src.contains("        _ => Params { mode: \"b\", t0: 3.5 },"), "{}", src);
+        assert!(!src.contains("1 =>"), "{}", src);
+    }
+}
@@ -0,0 +1,182 @@
+use rayon::prelude::*;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::Heu;
+
+/// 入力生成に使うシードの割り当て。
+#[derive(Debug, PartialEq)]
+pub enum Seeds {
+    /// ケース番号をそのままシードにする。
+    Case,
+    /// ケース i のシードを start + i とする (end まで)。
+    Range { start: u64, end: u64 },
+    /// シードファイルの i 行目をケース i のシードにする。
+    List(Vec<u64>),
+}
+
+impl Seeds {
+    /// `test.seeds` の指定を読み込む。
+    /// "A-B" ならシードの範囲、それ以外はシードファイルのパス、未指定ならケース番号。
+    pub fn load(spec: Option<&str>) -> io::Result<Self> {
+        let Some(spec) = spec.map(str::trim).filter(|s| !s.is_empty()) else {
+            return Ok(Seeds::Case);
+        };
+        if let Some((a, b)) = spec.split_once('-') {
+            if let (Ok(start), Ok(end)) = (a.trim().parse::<u64>(), b.trim().parse::<u64>()) {
+                if start > end {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("invalid seed range: {}", spec),
+                    ));
+                }
+                return Ok(Seeds::Range { start, end });
+            }
+        }
+        let content = fs::read_to_string(spec)
+            .map_err(|e| io::Error::new(e.kind(), format!("failed to read seeds file '{}': {}", spec, e)))?;
+        Self::parse_list(&content)
+            .map_err(|e| io::Error::new(e.kind(), format!("seeds file '{}': {}", spec, e)))
+    }
+
+    /// シードファイルの内容をパースする。空行は無視する。
+    pub fn parse_list(content: &str) -> io::Result<Self> {
+        let mut seeds = Vec::new();
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let seed = line.parse().map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("line {}: invalid seed '{}'", i + 1, line))
+            })?;
+            seeds.push(seed);
+        }
+        Ok(Seeds::List(seeds))
+    }
+
+    /// ケースのシード。割り当てが無いケースは None。
+    pub fn seed(&self, case: u32) -> Option<u64> {
+        match self {
+            Seeds::Case => Some(case as u64),
+            Seeds::Range { start, end } => Some(start + case as u64).filter(|s| s <= end),
+            Seeds::List(seeds) => seeds.get(case as usize).copied(),
+        }
+    }
+}
+
+/// ケースの入力ファイルを `gen` コマンドで並列に生成する。
+/// ケースをスレッド数に分けてまとめ、それぞれ一時ディレクトリにシードファイルを書いて
+/// `gen <seeds> --dir=<tmp>` を実行し、生成された `0000.txt` 以降を `{in_dir}/{case:04}.txt` に移動する。
+pub fn generate(gen: &str, seeds: &Seeds, in_dir: &str, cases: &[u32], threads: usize) -> io::Result<()> {
+    let lines = cases
+        .iter()
+        .map(|&c| {
+            seeds.seed(c).map(|s| (c, s)).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, format!("no seed for case {:04}", c))
+            })
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    if lines.is_empty() {
+        return Ok(());
+    }
+
+    let threads = threads.max(1);
+    let chunk_size = lines.len().div_ceil(threads);
+    let tmp_root = Path::new(in_dir).join(".gen");
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .map_err(io::Error::other)?;
+    let result = pool.install(|| {
+        lines
+            .par_chunks(chunk_size)
+            .enumerate()
+            .try_for_each(|(i, chunk)| generate_chunk(gen, in_dir, &tmp_root.join(i.to_string()), chunk))
+    });
+    let _ = fs::remove_dir_all(&tmp_root);
+    result
+}
+
+fn generate_chunk(gen: &str, in_dir: &str, tmp: &Path, chunk: &[(u32, u64)]) -> io::Result<()> {
+    if tmp.exists() {
+        fs::remove_dir_all(tmp)?;
+    }
+    fs::create_dir_all(tmp)?;
+    let seeds_file = tmp.join("seeds.txt");
+    let content: String = chunk.iter().map(|(_, s)| format!("{}\n", s)).collect();
+    fs::write(&seeds_file, content)?;
+
+    let out_dir = tmp.join("in");
+    let output = Heu::command_from_str(gen)?
+        .arg(&seeds_file)
+        .arg(format!("--dir={}", out_dir.display()))
+        .output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let msg = stderr.lines().find(|l| !l.trim().is_empty()).unwrap_or("gen failed");
+        return Err(io::Error::other(format!("gen failed: {}", msg.trim())));
+    }
+
+    for (i, (case, _)) in chunk.iter().enumerate() {
+        let generated = out_dir.join(format!("{:04}.txt", i));
+        if !generated.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("gen did not create {}", generated.display()),
+            ));
+        }
+        fs::rename(&generated, Path::new(in_dir).join(format!("{:04}.txt", case)))?;
+    }
+    fs::remove_dir_all(tmp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeds() {
+        assert_eq!(Seeds::load(None).unwrap(), Seeds::Case);
+        assert_eq!(Seeds::Case.seed(12), Some(12));
+
+        let range = Seeds::load(Some("1000-1999")).unwrap();
+        assert_eq!(range, Seeds::Range { start: 1000, end: 1999 });
+        assert_eq!(range.seed(5), Some(1005));
+        assert_eq!(range.seed(1000), None);
+        assert!(Seeds::load(Some("10-5")).is_err());
+
+        let list = Seeds::parse_list("7\n\n 42 \n").unwrap();
+        assert_eq!(list.seed(1), Some(42));
+        assert_eq!(list.seed(2), None);
+        assert!(Seeds::parse_list("1\nabc\n").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_generate() {
+        let dir = std::env::temp_dir().join(format!("heu-gen-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        // 公式の gen と同じく、シードファイルの i 行目から {dir}/{i:04}.txt を作る
+        let script = dir.join("gen.sh");
+        fs::write(
+            &script,
+            "d=${2#--dir=}; mkdir -p $d; i=0; while read s; do echo seed=$s > $d/$(printf %04d $i).txt; i=$((i+1)); done < $1\n",
+        )
+        .unwrap();
+        let in_dir = dir.join("in");
+        let in_dir = in_dir.to_str().unwrap();
+        fs::create_dir_all(in_dir).unwrap();
+
+        let gen = format!("sh {}", script.display());
+        generate(&gen, &Seeds::Range { start: 100, end: 199 }, in_dir, &[3, 5, 8], 2).unwrap();
+        for (case, seed) in [(3, 103), (5, 105), (8, 108)] {
+            let content = fs::read_to_string(format!("{}/{:04}.txt", in_dir, case)).unwrap();
+            assert_eq!(content.trim(), format!("seed={}", seed));
+        }
+        assert!(!Path::new(in_dir).join(".gen").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
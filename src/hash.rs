@@ -0,0 +1,139 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// 64 ビットの FNV-1a。実行をまたいで同じ値になるので、ファイルの同一性の確認に使う。
+#[derive(Debug, Clone, Copy)]
+pub struct Fnv64(u64);
+
+impl Default for Fnv64 {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv64 {
+    pub fn update(&mut self, bytes: &[u8]) -> &mut Self {
+        for &b in bytes {
+            self.0 ^= b as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+        self
+    }
+
+    /// 区切りを付けて追加する。続けて追加した値の境目がずれても同じ値にならないようにする。
+    pub fn field(&mut self, bytes: &[u8]) -> &mut Self {
+        self.update(&(bytes.len() as u64).to_le_bytes()).update(bytes)
+    }
+
+    /// 16 桁の16進数。
+    pub fn hex(&self) -> String {
+        format!("{:016x}", self.0)
+    }
+}
+
+/// 実行コマンドのハッシュ。コマンド文字列と、引数のうち存在するファイル (実行ファイルやスクリプト) の中身から計算する。
+pub fn command_hash(cmd: &str) -> io::Result<String> {
+    let parts = split(cmd)?;
+    let mut h = Fnv64::default();
+    h.field(cmd.as_bytes());
+    for part in &parts {
+        if Path::new(part).is_file() {
+            h.field(&fs::read(part)?);
+        }
+    }
+    Ok(h.hex())
+}
+
+/// 実行されるものの中身まで含めたコマンドのハッシュ。
+/// `cargo run` のコマンドは引数の Cargo.toml をハッシュしてもソースの変更が分からないので、
+/// 同じ引数で `cargo build` し、できた実行ファイルの中身から計算する。それ以外は [`command_hash`] と同じ。
+pub fn executable_hash(cmd: &str) -> io::Result<String> {
+    let parts = split(cmd)?;
+    let Some(args) = cargo_build_args(&parts) else {
+        return command_hash(cmd);
+    };
+    let output = Command::new(&parts[0]).args(&args).stdin(Stdio::null()).stderr(Stdio::inherit()).output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!("failed to build '{}'", cmd)));
+    }
+    let mut h = Fnv64::default();
+    h.field(cmd.as_bytes());
+    let mut executables = 0;
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let Ok(message) = serde_json::from_str::<serde_json::Value>(line) else {
+            continue;
+        };
+        if let Some(exe) = message.get("executable").and_then(|v| v.as_str()) {
+            h.field(&fs::read(exe)?);
+            executables += 1;
+        }
+    }
+    if executables == 0 {
+        return Err(io::Error::other(format!("no executable built by '{}'", cmd)));
+    }
+    Ok(h.hex())
+}
+
+/// `cargo [+toolchain] run ARGS [-- ...]` なら、同じ ARGS で実行ファイルのパスを出力させる `cargo build` の引数。
+/// それ以外は None。
+fn cargo_build_args(parts: &[String]) -> Option<Vec<String>> {
+    if Path::new(parts.first()?).file_stem()? != "cargo" {
+        return None;
+    }
+    let args: Vec<&String> = parts[1..].iter().take_while(|a| *a != "--").collect();
+    let sub = args.iter().position(|a| !a.starts_with('+') && !a.starts_with('-'))?;
+    if args[sub] != "run" && args[sub] != "r" {
+        return None;
+    }
+    let mut ret: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+    ret[sub] = "build".to_string();
+    ret.extend(["-q", "--message-format=json-render-diagnostics"].map(String::from));
+    Some(ret)
+}
+
+fn split(cmd: &str) -> io::Result<Vec<String>> {
+    shlex::split(cmd).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("invalid command: {}", cmd)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fnv64() {
+        assert_eq!(Fnv64::default().hex(), "cbf29ce484222325");
+        assert_eq!(Fnv64::default().update(b"a").hex(), "af63dc4c8601ec8c");
+        assert_eq!(Fnv64::default().update(b"foobar").hex(), "85944171f73967e8");
+        let a = Fnv64::default().field(b"ab").field(b"c").hex();
+        let b = Fnv64::default().field(b"a").field(b"bc").hex();
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_cargo_build_args() {
+        let args = |cmd: &str| cargo_build_args(&shlex::split(cmd).unwrap()).map(|a| a.join(" "));
+        assert_eq!(
+            args("cargo run --manifest-path tools/Cargo.toml --bin vis --target-dir=tools/target -r").as_deref(),
+            Some("build --manifest-path tools/Cargo.toml --bin vis --target-dir=tools/target -r -q --message-format=json-render-diagnostics")
+        );
+        assert_eq!(args("cargo +nightly r -r -- x").as_deref(), Some("+nightly build -r -q --message-format=json-render-diagnostics"));
+        assert_eq!(args("cargo build"), None);
+        assert_eq!(args("./target/release/vis"), None);
+    }
+
+    #[test]
+    fn test_command_hash() {
+        let dir = std::env::temp_dir().join(format!("heu-hash-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("sol.sh");
+        fs::write(&script, "echo 1").unwrap();
+        let cmd = format!("sh {}", script.display());
+        let first = command_hash(&cmd).unwrap();
+        assert_eq!(command_hash(&cmd).unwrap(), first);
+        fs::write(&script, "echo 2").unwrap();
+        assert_ne!(command_hash(&cmd).unwrap(), first);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,286 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{format_with_commas, CaseResult, Config, Score};
+
+/// 1回の実行の記録。`{out_dir}/history/{id}.json` に保存される。
+#[derive(Serialize, Deserialize)]
+pub struct RunRecord {
+    /// 実行 ID (UTC の "YYYYMMDD-HHMMSS")。
+    pub id: String,
+    /// 実行開始時刻 (UTC, ISO 8601)。
+    pub timestamp: String,
+    /// 実行時の git コミット。未コミットの変更がある場合は "-dirty" が付く。
+    pub git_commit: Option<String>,
+    pub config: Config,
+    /// 成功したケースのスコア合計。
+    pub total: Score,
+    pub cases: Vec<CaseResult>,
+    /// Ctrl-C で中断された実行か。
+    #[serde(default)]
+    pub interrupted: bool,
+    /// 中断されたために実行しなかったケース。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pending: Vec<u32>,
+    /// 実行コマンド (`test.bin`) のハッシュ。`--resume` で同じバイナリの実行を探すのに使う。
+    #[serde(default)]
+    pub bin_hash: Option<String>,
+    /// `--resume` / `--rerun-failed` で結果を引き継いだ実行の ID。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resumed_from: Option<String>,
+}
+
+impl RunRecord {
+    pub fn new(config: &Config, cases: Vec<CaseResult>) -> Self {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let (date, time) = utc_date_time(secs);
+        let total = cases
+            .iter()
+            .filter_map(|r| r.ok_score())
+            .fold(Score::zero(config.test.score_type), |acc, s| acc + s);
+        Self {
+            id: format!(
+                "{:04}{:02}{:02}-{:02}{:02}{:02}",
+                date.0, date.1, date.2, time.0, time.1, time.2
+            ),
+            timestamp: format!(
+                "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+                date.0, date.1, date.2, time.0, time.1, time.2
+            ),
+            git_commit: git_commit(),
+            config: config.clone(),
+            total,
+            cases,
+            interrupted: false,
+            pending: Vec::new(),
+            bin_hash: None,
+            resumed_from: None,
+        }
+    }
+
+    pub fn failed(&self) -> usize {
+        self.cases.iter().filter(|r| !r.verdict.is_ok()).count()
+    }
+}
+
+/// 実行履歴の保存先ディレクトリ。
+pub fn history_dir(out_dir: &str) -> PathBuf {
+    Path::new(out_dir).join("history")
+}
+
+/// 実行記録を保存する。同じ秒に複数回実行した場合は ID に連番を付ける。
+pub fn save(out_dir: &str, record: &mut RunRecord) -> io::Result<PathBuf> {
+    let dir = history_dir(out_dir);
+    fs::create_dir_all(&dir)?;
+    let base = record.id.clone();
+    // 同時に保存する別の実行と同じ ID にならないよう、空のファイルを create_new で作って ID を確保する
+    let mut seq = 0;
+    let path = loop {
+        if seq > 0 {
+            record.id = format!("{}-{}", base, seq);
+        }
+        let path = dir.join(format!("{}.json", record.id));
+        match fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(_) => break path,
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => seq += 1,
+            Err(e) => return Err(e),
+        }
+    };
+    // 書き込み途中で中断されても壊れた記録が残らないよう、一時ファイルに書いてから確保したファイルに rename する
+    let tmp = dir.join(format!("{}.json.tmp", record.id));
+    let written = serde_json::to_string_pretty(record)
+        .map_err(io::Error::other)
+        .and_then(|json| fs::write(&tmp, json))
+        .and_then(|_| fs::rename(&tmp, &path));
+    if let Err(e) = written {
+        let _ = fs::remove_file(&tmp);
+        let _ = fs::remove_file(&path);
+        return Err(e);
+    }
+    Ok(path)
+}
+
+/// 保存されている実行記録を ID (= 時刻) の昇順で読み込む。読み込めない記録は警告を出して読み飛ばす。
+pub fn load_all(out_dir: &str) -> io::Result<Vec<RunRecord>> {
+    let dir = history_dir(out_dir);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut paths: Vec<PathBuf> = fs::read_dir(&dir)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+        // 空のファイルは保存中の実行が確保した ID
+        .filter(|p| fs::metadata(p).is_ok_and(|m| m.len() > 0))
+        .collect();
+    paths.sort_by_cached_key(|p| id_sort_key(&p.file_stem().unwrap_or_default().to_string_lossy()));
+    Ok(paths
+        .iter()
+        .filter_map(|p| match load_file(p) {
+            Ok(r) => Some(r),
+            Err(e) => {
+                eprintln!("Warning: skipping unreadable run record: {}", e);
+                None
+            }
+        })
+        .collect())
+}
+
+/// ID を並べ替えるためのキー。"YYYYMMDD-HHMMSS-N" の連番 N は数値として比較する。
+fn id_sort_key(id: &str) -> (String, u64) {
+    // 時刻部分は "YYYYMMDD-HHMMSS" の 15 文字
+    match id.get(15..).and_then(|rest| rest.strip_prefix('-')).and_then(|n| n.parse().ok()) {
+        Some(seq) => (id[..15].to_string(), seq),
+        None => (id.to_string(), 0),
+    }
+}
+
+/// ID を指定して実行記録を読み込む。
+pub fn load(out_dir: &str, id: &str) -> io::Result<RunRecord> {
+    let path = history_dir(out_dir).join(format!("{}.json", id));
+    if !path.exists() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("run '{}' not found in history", id)));
+    }
+    load_file(&path)
+}
+
+fn load_file(path: &Path) -> io::Result<RunRecord> {
+    let content = fs::read_to_string(path)?;
+    serde_json::from_str(&content).map_err(|e| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e))
+    })
+}
+
+/// 実行履歴を一覧表示する。`limit` を指定すると新しいものから最大その件数を表示する。
+pub fn print_list(out_dir: &str, limit: Option<usize>) -> io::Result<()> {
+    let records = load_all(out_dir)?;
+    let skip = limit.map_or(0, |l| records.len().saturating_sub(l));
+    println!("{:<17} {:<14} {:>6} {:>6} {:>15}", "ID", "COMMIT", "CASES", "FAILED", "TOTAL");
+    for r in records.iter().skip(skip) {
+        println!(
+            "{:<17} {:<14} {:>6} {:>6} {:>15}{}",
+            r.id,
+            r.git_commit.as_deref().unwrap_or("-"),
+            r.cases.len(),
+            r.failed(),
+            format_with_commas(r.total),
+            if r.interrupted { format!(" INTERRUPTED ({} pending)", r.pending.len()) } else { String::new() }
+        );
+    }
+    Ok(())
+}
+
+/// 現在の git コミットの短縮ハッシュを返す。git 管理外なら None。
+fn git_commit() -> Option<String> {
+    let output = Command::new("git").args(["rev-parse", "--short", "HEAD"]).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let mut commit = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let dirty = Command::new("git")
+        .args(["status", "--porcelain", "--untracked-files=no"])
+        .output()
+        .is_ok_and(|o| !o.stdout.is_empty());
+    if dirty {
+        commit.push_str("-dirty");
+    }
+    Some(commit)
+}
+
+/// UNIX 時刻 (秒) を UTC の ((年, 月, 日), (時, 分, 秒)) に変換する。
+fn utc_date_time(secs: u64) -> ((i64, u32, u32), (u32, u32, u32)) {
+    let days = (secs / 86400) as i64;
+    let rem = (secs % 86400) as u32;
+    // Howard Hinnant の civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let y = yoe + era * 400 + i64::from(m <= 2);
+    ((y, m, d), (rem / 3600, rem % 3600 / 60, rem % 60))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utc_date_time() {
+        assert_eq!(utc_date_time(0), ((1970, 1, 1), (0, 0, 0)));
+        // 2024-02-29T12:34:56Z
+        assert_eq!(utc_date_time(1709210096), ((2024, 2, 29), (12, 34, 56)));
+    }
+
+    #[test]
+    fn test_save_and_load_roundtrip() {
+        let out_dir = std::env::temp_dir().join(format!("heu-history-test-{}", std::process::id()));
+        let out_dir = out_dir.to_str().unwrap();
+        let config = Config::default_config();
+        let err = io::Error::new(io::ErrorKind::NotFound, "missing");
+        let cases = vec![CaseResult::io_error(3, crate::CaseFiles::default(), &err)];
+
+        let mut first = RunRecord::new(&config, cases);
+        first.id = "20240101-000000".to_string();
+        save(out_dir, &mut first).unwrap();
+        let mut second = RunRecord::new(&config, Vec::new());
+        second.id = "20240101-000000".to_string();
+        save(out_dir, &mut second).unwrap();
+        assert_eq!(second.id, "20240101-000000-1");
+
+        let records = load_all(out_dir).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].cases[0].case, 3);
+        assert_eq!(records[0].failed(), 1);
+        assert_eq!(load(out_dir, "20240101-000000-1").unwrap().cases.len(), 0);
+        assert!(load(out_dir, "nope").is_err());
+
+        // 壊れた記録は読み飛ばす
+        fs::write(history_dir(out_dir).join("20240101-000001.json"), "{").unwrap();
+        assert_eq!(load_all(out_dir).unwrap().len(), 2);
+
+        fs::remove_dir_all(out_dir).unwrap();
+    }
+
+    #[test]
+    fn test_save_concurrently_keeps_all_records() {
+        let out_dir = std::env::temp_dir().join(format!("heu-history-concurrent-test-{}", std::process::id()));
+        let out_dir = out_dir.to_str().unwrap();
+        let config = Config::default_config();
+        let ids: Vec<String> = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..8)
+                .map(|_| {
+                    s.spawn(|| {
+                        let mut r = RunRecord::new(&config, Vec::new());
+                        r.id = "20240101-000000".to_string();
+                        save(out_dir, &mut r).unwrap();
+                        r.id
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        let mut unique = ids.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), 8);
+        assert_eq!(load_all(out_dir).unwrap().len(), 8);
+        fs::remove_dir_all(out_dir).unwrap();
+    }
+
+    #[test]
+    fn test_id_sort_key() {
+        let mut ids = vec!["20240101-000000-10", "20240101-000001", "20240101-000000-2", "20240101-000000"];
+        ids.sort_by_key(|id| id_sort_key(id));
+        assert_eq!(ids, ["20240101-000000", "20240101-000000-2", "20240101-000000-10", "20240101-000001"]);
+    }
+}
@@ -1,25 +1,82 @@
+pub mod baseline;
+pub mod best;
+pub mod cache;
+pub mod cases;
+pub mod compare;
+pub mod dashboard;
+pub mod features;
+pub mod gen;
+pub mod hash;
+pub mod history;
+pub mod output;
+pub mod process;
+pub mod report;
+pub mod resume;
+pub mod score;
+pub mod sequential;
+pub mod stats;
+pub mod sweep;
+pub mod tune;
+pub mod watch;
+
 use rayon::prelude::*;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
 use std::fs;
-use std::io;
+use std::io::{self, IsTerminal, Write};
 use std::process::Command;
-use std::sync::mpsc;
-use std::time::Instant;
-
-#[derive(Serialize, Deserialize)]
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::sync::{mpsc, Mutex};
+use std::time::Duration;
+
+use baseline::{Baseline, WinLoss};
+use compare::Profile;
+use dashboard::Dashboard;
+use best::BestScores;
+use output::OutputFormat;
+use process::ProcessOutput;
+use sequential::{Decision, SequentialConfig, SequentialResult, SequentialTest};
+use sweep::SweepConfig;
+use tune::TuneConfig;
+use watch::WatchConfig;
+pub use process::ResourceUsage;
+pub use score::{Score, ScoreType};
+pub use stats::Summary;
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Config {
     pub build: BuildConfig,
     pub test: TestConfig,
+    /// 名前付きのケース集合。ケース指定で "@name" として参照する。
+    #[serde(default)]
+    pub sets: BTreeMap<String, String>,
+    /// `cargo heu sweep` で試すパラメータ。
+    #[serde(default)]
+    pub sweep: SweepConfig,
+    /// `cargo heu tune` で探索するパラメータ空間。
+    #[serde(default)]
+    pub tune: TuneConfig,
+    /// `cargo heu compare` で比べる実行方法。
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+    /// ベースラインとの差が決まった時点で打ち切る実行の設定。
+    #[serde(default)]
+    pub sequential: SequentialConfig,
+    /// `cargo heu --watch` で監視するファイル。
+    #[serde(default)]
+    pub watch: WatchConfig,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct BuildConfig {
     pub enable: bool,
     pub command: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct TestConfig {
     pub bin: String,
     pub cases: String,
@@ -31,7 +88,72 @@ pub struct TestConfig {
     pub vis: String,
     pub tester: String,
     pub score_regex: String,
+    /// スコアの型 ("int" または "float")。
+    #[serde(default)]
+    pub score_type: ScoreType,
     pub comment_regex: String,
+    /// 1ケースあたりの制限時間(秒)。超えたら kill して TLE とする。
+    #[serde(default)]
+    pub time_limit: Option<f64>,
+    /// 比較対象の実行 ("previous", "best" または実行 ID)。
+    #[serde(default)]
+    pub baseline: Option<String>,
+    /// スコアの最適化方向。
+    #[serde(default)]
+    pub objective: Objective,
+    /// 入力生成コマンド。引数としてシードファイルと `--dir=<出力先>` が追加される。
+    /// 指定すると、入力ファイルが無いケースを実行前に生成する。
+    #[serde(default)]
+    pub gen: Option<String>,
+    /// 入力生成のシード。"A-B" ならケース i のシードは A+i、それ以外はシードファイルのパス。
+    /// 省略時はケース番号をシードにする。
+    #[serde(default)]
+    pub seeds: Option<String>,
+    /// 結果の出力形式。
+    #[serde(default)]
+    pub format: OutputFormat,
+    /// 端末に出力する場合、進捗や実行中のケースを表示するダッシュボードを使う。
+    #[serde(default)]
+    pub tui: bool,
+    /// バイナリ・入力・環境変数が同じケースは実行せず、保存した結果を使う。
+    #[serde(default)]
+    pub cache: bool,
+    /// ソリューションが参照する環境変数のうち、キャッシュのキーに含めるもの。
+    /// スイープ・探索で渡す変数は常に含まれるが、ここに無い実行時の環境変数の違いは無視される。
+    #[serde(default)]
+    pub cache_env: Vec<String>,
+}
+
+/// スコアを最大化するか最小化するか。
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Objective {
+    #[default]
+    Max,
+    Min,
+}
+
+impl Objective {
+    /// `a` が `b` より良ければ Greater を返す。
+    pub fn compare(self, a: Score, b: Score) -> Ordering {
+        match self {
+            Objective::Max => a.cmp_value(&b),
+            Objective::Min => b.cmp_value(&a),
+        }
+    }
+
+    pub fn is_better(self, a: Score, b: Score) -> bool {
+        self.compare(a, b) == Ordering::Greater
+    }
+}
+
+impl fmt::Display for Objective {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Objective::Max => write!(f, "max"),
+            Objective::Min => write!(f, "min"),
+        }
+    }
 }
 
 impl Config {
@@ -57,9 +179,158 @@ impl Config {
                 vis: "cargo run --manifest-path tools/Cargo.toml --bin vis --target-dir=tools/target -r".to_string(),
                 tester: "cargo run --manifest-path tools/Cargo.toml --bin tester --target-dir=tools/target -r".to_string(),
                 score_regex: "Score = (\\d+)".to_string(),
+                score_type: ScoreType::Int,
                 comment_regex: "^# (.*)$".to_string(),
+                time_limit: None,
+                baseline: None,
+                objective: Objective::Max,
+                gen: None,
+                seeds: None,
+                format: OutputFormat::Human,
+                tui: false,
+                cache: false,
+                cache_env: Vec::new(),
             },
+            sets: BTreeMap::new(),
+            sweep: SweepConfig::default(),
+            tune: TuneConfig::default(),
+            profiles: BTreeMap::new(),
+            sequential: SequentialConfig::default(),
+            watch: WatchConfig::default(),
+        }
+    }
+
+    /// Option の値を TOML の1行にする。None の場合は例の値でコメントアウトする。
+    fn toml_optional_line<T: std::fmt::Display>(key: &str, value: Option<T>, example: &str) -> String {
+        match value {
+            Some(v) => format!("{} = {}", key, v),
+            None => format!("# {} = {}", key, example),
+        }
+    }
+
+    /// `[sets]` の中身。空の場合は例をコメントアウトして出す。
+    fn sets_toml(&self) -> String {
+        if self.sets.is_empty() {
+            return "# small = \"0-9\"".to_string();
+        }
+        self.sets
+            .iter()
+            .map(|(name, spec)| {
+                format!(
+                    "\"{}\" = \"{}\"",
+                    Self::escape_toml_basic_string(name),
+                    Self::escape_toml_basic_string(spec)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// `[sweep]` の中身。空の場合は例をコメントアウトして出す。
+    fn sweep_toml(&self) -> String {
+        let sweep = &self.sweep;
+        if sweep.is_empty() {
+            return "# grid = { T0 = [1000, 2000], T1 = [10, 100] }\n# list = [{ T0 = 1500, T1 = 50 }]".to_string();
+        }
+        let table = |m: &mut dyn Iterator<Item = (String, String)>| {
+            let items: Vec<String> = m.map(|(k, v)| format!("\"{}\" = {}", Self::escape_toml_basic_string(&k), v)).collect();
+            format!("{{ {} }}", items.join(", "))
+        };
+        let mut lines = Vec::new();
+        if !sweep.grid.is_empty() {
+            let grid = table(&mut sweep.grid.iter().map(|(k, vs)| {
+                let vs: Vec<String> = vs.iter().map(|v| v.to_toml()).collect();
+                (k.clone(), format!("[{}]", vs.join(", ")))
+            }));
+            lines.push(format!("grid = {}", grid));
+        }
+        if !sweep.list.is_empty() {
+            let list: Vec<String> = sweep
+                .list
+                .iter()
+                .map(|m| table(&mut m.iter().map(|(k, v)| (k.clone(), v.to_toml()))))
+                .collect();
+            lines.push(format!("list = [{}]", list.join(", ")));
+        }
+        lines.join("\n")
+    }
+
+    /// `[profiles]` の中身。空の場合は例をコメントアウトして出す。
+    fn profiles_toml(&self) -> String {
+        if self.profiles.is_empty() {
+            return "# fast = { bin = \"./target/release/fast\", build = \"cargo build --release --bin fast\", env = { T0 = 1000 } }"
+                .to_string();
+        }
+        let string = |s: &str| format!("\"{}\"", Self::escape_toml_basic_string(s));
+        self.profiles
+            .iter()
+            .map(|(name, p)| {
+                let mut items = Vec::new();
+                if let Some(bin) = &p.bin {
+                    items.push(format!("bin = {}", string(bin)));
+                }
+                if let Some(build) = &p.build {
+                    items.push(format!("build = {}", string(build)));
+                }
+                if !p.env.is_empty() {
+                    let env: Vec<String> = p.env.iter().map(|(k, v)| format!("{} = {}", string(k), v.to_toml())).collect();
+                    items.push(format!("env = {{ {} }}", env.join(", ")));
+                }
+                format!("{} = {{ {} }}", string(name), items.join(", "))
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// `tune.buckets` の行。空の場合は例をコメントアウトして出す。
+    fn tune_buckets_toml(&self) -> String {
+        if self.tune.buckets.is_empty() {
+            return "# buckets = { N = [100.0, 200.0] }".to_string();
         }
+        let items: Vec<String> = self
+            .tune
+            .buckets
+            .iter()
+            .map(|(name, edges)| {
+                let edges: Vec<String> = edges.iter().map(|&e| sweep::ParamValue::Float(e).to_toml()).collect();
+                format!("\"{}\" = [{}]", Self::escape_toml_basic_string(name), edges.join(", "))
+            })
+            .collect();
+        format!("buckets = {{ {} }}", items.join(", "))
+    }
+
+    /// `[tune.params]` の中身。空の場合は例をコメントアウトして出す。
+    fn tune_params_toml(&self) -> String {
+        use tune::ParamSpec;
+        let params = &self.tune.params;
+        if params.is_empty() {
+            return [
+                "# T0 = { type = \"float\", min = 100.0, max = 10000.0, log = true }",
+                "# T1 = { type = \"int\", min = 1, max = 100 }",
+                "# MODE = { type = \"choice\", values = [\"greedy\", \"beam\"] }",
+            ]
+            .join("\n");
+        }
+        let float = |v: f64| sweep::ParamValue::Float(v).to_toml();
+        params
+            .iter()
+            .map(|(name, spec)| {
+                let body = match spec {
+                    ParamSpec::Int { min, max, log } => {
+                        format!("type = \"int\", min = {}, max = {}, log = {}", min, max, log)
+                    }
+                    ParamSpec::Float { min, max, log } => {
+                        format!("type = \"float\", min = {}, max = {}, log = {}", float(*min), float(*max), log)
+                    }
+                    ParamSpec::Choice { values } => {
+                        let values: Vec<String> = values.iter().map(|v| v.to_toml()).collect();
+                        format!("type = \"choice\", values = [{}]", values.join(", "))
+                    }
+                };
+                format!("\"{}\" = {{ {} }}", Self::escape_toml_basic_string(name), body)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
     }
 
     pub fn generate_toml_with_comments(&self) -> String {
@@ -73,7 +344,7 @@ command = "{}"
 [test]
 # 実行バイナリのパス
 bin = "{}"
-# テストケース範囲 (例: "0-9", "0 1 3-5")
+# テストケース (例: "0-9", "0 1 3-5", "0-99 ^13", "0-999:10", "rand:50", "@small", "failed", "worst:10")
 cases = "{}"
 # 並列スレッド数
 threads = {}
@@ -91,8 +362,82 @@ vis = "{}"
 tester = "{}"
 # ビジュアライザ出力からスコアを抽出する正規表現（第1キャプチャを数値として使用）
 score_regex = "{}"
+# スコアの型 ("int": 符号付き整数, "float": 小数)
+score_type = "{}"
 # stderr の各行からコメントを抽出する正規表現（第1キャプチャをコメント本文として使用）
 comment_regex = "{}"
+# 1ケースあたりの制限時間(秒)。超過したケースは kill され TLE となる
+{}
+# ケースごとのスコアを比較する過去の実行 ("previous", "best" または実行 ID)
+{}
+# スコアの最適化方向 ("max": 大きいほど良い, "min": 小さいほど良い)。相対スコアの計算に使う
+objective = "{}"
+# 結果の出力形式 ("human", "json", "jsonl", "csv", "tsv")
+format = "{}"
+# 端末では進捗バー・ETA・実行中のケース・悪いケースを表示するダッシュボードを使う (パイプへの出力では使わない)
+tui = {}
+# 実行バイナリ・入力ファイル・環境変数が前回と同じケースは実行せず、out_dir/cache に保存した結果を使う
+cache = {}
+# キャッシュのキーに値を含める環境変数。ここに無い環境変数を変えてもキャッシュした結果が使われる
+cache_env = [{}]
+# 入力生成コマンド (引数としてシードファイルと --dir=<出力先> が追加される)。入力ファイルが無いケースを実行前に生成する
+{}
+# 入力生成のシード ("A-B": ケース i のシードは A+i, それ以外: シードファイルのパス)。省略時はケース番号
+{}
+
+# 名前付きのケース集合。ケース指定で "@small" のように参照する
+[sets]
+{}
+
+# cargo heu sweep で試すパラメータ。各値を環境変数としてソリューションに渡す
+# grid: 全ての組み合わせを試す, list: 組み合わせを直接指定する
+[sweep]
+{}
+
+# cargo heu compare --profile-a NAME --profile-b NAME で比べる実行方法
+# bin: 実行コマンド (省略時は test.bin), build: 追加のビルドコマンド, env: 環境変数
+[profiles]
+{}
+
+# ベースライン (test.baseline / -b) との差が決まった時点で実行を打ち切る (--sequential でも有効になる)
+# テストケースを batch 件ずつ実行し、1回ごとに Wilcoxon の符号付き順位検定を行う。有意水準は 0.05 を検定の回数で割った値
+[sequential]
+enable = {}
+# 1回に実行するケース数
+batch = {}
+# 比較できたケースがこれより少ないうちは打ち切らない
+min_cases = {}
+
+# cargo heu --watch で監視するファイル。変更されるとビルドからやり直す (実行中なら中断する)
+[watch]
+# 監視するファイルの glob ("*", "?", "**")。隠しディレクトリと target の下は探さない
+paths = [{}]
+# 最後の変更からこの秒数だけ変更が無ければ再実行する
+debounce = {}
+
+# cargo heu tune の設定。[tune.params] のパラメータを環境変数としてソリューションに渡し、最良の値を探す
+[tune]
+# 探索方法 ("random": ランダム, "local": ランダムの後に最良の近傍を探す, "halving": 少ないケースで絞り込む)
+strategy = "{}"
+# 試行回数 (halving では最初の候補数)
+trials = {}
+# 乱数のシード。同じシードで再実行すると試行ログ (out_dir/tune/trials.jsonl) から続きを探索する
+seed = {}
+# halving で各段に残す割合の逆数
+eta = {}
+# 最良のパラメータの出力先 (.json なら JSON、それ以外は NAME=VALUE の行)。省略時は out_dir/tune/best.env
+{}
+# 入力ファイルの1行目の各値に付ける特徴量の名前 ("_" は使わない)。指定すると区間ごとの最良のパラメータを表にする
+features = [{}]
+# 特徴量ごとの区間の区切り。省略した特徴量は値の分布から bins 等分する
+{}
+bins = {}
+# 区間ごとのパラメータを返す Rust の関数の出力先。省略時は out_dir/tune/table.rs
+{}
+
+# type: "int" / "float" (min, max, log = true で対数スケール), "choice" (values)
+[tune.params]
+{}
 "#,
             self.build.enable,
             self.build.command,
@@ -106,7 +451,70 @@ comment_regex = "{}"
             self.test.vis,
             self.test.tester,
             Self::escape_toml_basic_string(&self.test.score_regex),
+            self.test.score_type,
             Self::escape_toml_basic_string(&self.test.comment_regex),
+            Self::toml_optional_line("time_limit", self.test.time_limit, "2.0"),
+            Self::toml_optional_line(
+                "baseline",
+                self.test.baseline.as_ref().map(|b| format!("\"{}\"", Self::escape_toml_basic_string(b))),
+                "\"previous\"",
+            ),
+            self.test.objective,
+            self.test.format,
+            self.test.tui,
+            self.test.cache,
+            self.test
+                .cache_env
+                .iter()
+                .map(|v| format!("\"{}\"", Self::escape_toml_basic_string(v)))
+                .collect::<Vec<_>>()
+                .join(", "),
+            Self::toml_optional_line(
+                "gen",
+                self.test.gen.as_ref().map(|g| format!("\"{}\"", Self::escape_toml_basic_string(g))),
+                "\"cargo run --manifest-path tools/Cargo.toml --bin gen --target-dir=tools/target -r --\"",
+            ),
+            Self::toml_optional_line(
+                "seeds",
+                self.test.seeds.as_ref().map(|s| format!("\"{}\"", Self::escape_toml_basic_string(s))),
+                "\"tools/seeds.txt\"",
+            ),
+            self.sets_toml(),
+            self.sweep_toml(),
+            self.profiles_toml(),
+            self.sequential.enable,
+            self.sequential.batch,
+            self.sequential.min_cases,
+            self.watch
+                .paths
+                .iter()
+                .map(|p| format!("\"{}\"", Self::escape_toml_basic_string(p)))
+                .collect::<Vec<_>>()
+                .join(", "),
+            sweep::ParamValue::Float(self.watch.debounce).to_toml(),
+            self.tune.strategy,
+            self.tune.trials,
+            self.tune.seed,
+            self.tune.eta,
+            Self::toml_optional_line(
+                "output",
+                self.tune.output.as_ref().map(|o| format!("\"{}\"", Self::escape_toml_basic_string(o))),
+                "\"best.env\"",
+            ),
+            self.tune
+                .features
+                .iter()
+                .map(|f| format!("\"{}\"", Self::escape_toml_basic_string(f)))
+                .collect::<Vec<_>>()
+                .join(", "),
+            self.tune_buckets_toml(),
+            self.tune.bins,
+            Self::toml_optional_line(
+                "table",
+                self.tune.table.as_ref().map(|t| format!("\"{}\"", Self::escape_toml_basic_string(t))),
+                "\"src/table.rs\"",
+            ),
+            self.tune_params_toml(),
         )
     }
 }
@@ -118,49 +526,220 @@ pub struct Heu {
     cases: Vec<u32>,
     score_regex: Regex,
     comment_regex: Regex,
+    /// キャッシュのキーに使う実行コマンドのハッシュ。ビルドのたびに計算し直す。
+    /// Some(None) は計算できなかったことを表す (キャッシュを使わない)。
+    command_hash: Mutex<Option<Option<String>>>,
+    /// ウォッチモードで実行中か。ビルドを中断できるよう出力を取り込んで実行する。
+    watching: AtomicBool,
+}
+
+/// 1ケースの判定結果。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Verdict {
+    Ok,
+    /// 異常終了 (終了コードまたはシグナル)。
+    Re { code: Option<i32>, signal: Option<i32> },
+    /// 制限時間超過。
+    Tle,
+    /// ビジュアライザ出力からスコアが得られなかった。
+    Wa,
+    /// ビジュアライザの実行に失敗した。
+    VisError(String),
+    /// 入力の読み込みや出力の書き込みに失敗した。
+    IoError(String),
+}
+
+impl Verdict {
+    pub fn is_ok(&self) -> bool {
+        *self == Verdict::Ok
+    }
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Verdict::Ok => write!(f, "OK"),
+            Verdict::Re { signal: Some(sig), .. } => write!(f, "RE({})", signal_name(*sig)),
+            Verdict::Re { code: Some(code), .. } => write!(f, "RE(exit {})", code),
+            Verdict::Re { .. } => write!(f, "RE"),
+            Verdict::Tle => write!(f, "TLE"),
+            Verdict::Wa => write!(f, "WA"),
+            Verdict::VisError(msg) => write!(f, "VIS_ERROR({})", msg),
+            Verdict::IoError(msg) => write!(f, "IO_ERROR({})", msg),
+        }
+    }
+}
+
+/// 1ケースの入出力ファイルのパス。
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CaseFiles {
+    pub inf: String,
+    pub outf: String,
+    /// stderr の保存先。
+    pub errf: String,
 }
 
 /// 1ケースの実行結果。
+/// 実行履歴には visout と stderr を除いた内容が保存される。
+#[derive(Clone, Serialize, Deserialize)]
 pub struct CaseResult {
     pub case: u32,
     pub inf: String,
     pub outf: String,
+    #[serde(default)]
+    pub errf: String,
+    #[serde(skip)]
     pub visout: String,
+    #[serde(skip)]
     pub stderr: String,
     pub elapsed: f64,
-    pub score: u64,
-    comment_regex: Regex,
+    /// ビジュアライザ出力から得たスコア。見つからなかった場合は None。
+    pub score: Option<Score>,
+    pub verdict: Verdict,
+    /// ソリューション (use_tester の場合は tester) の終了コード。
+    pub exit_code: Option<i32>,
+    /// ソリューションを終了させたシグナル番号。
+    pub signal: Option<i32>,
+    /// stderr から抽出した Rust の panic メッセージ。
+    pub panic_message: Option<String>,
+    /// ピークメモリと CPU 時間。計測できない環境では None。
+    pub usage: Option<ResourceUsage>,
+    /// stderr から抽出したコメントを "/" で結合したもの。
+    pub comments: String,
+    /// 実行せずにキャッシュした結果を使った。
+    #[serde(default)]
+    pub cached: bool,
 }
 
 impl CaseResult {
+    /// 実行結果とビジュアライザ出力から結果を作る。
+    /// `vis` はビジュアライザの stdout、または失敗理由。評価しなかった場合は None。
+    /// `score` は vis から抽出したスコア。
     pub fn new(
         case: u32,
-        inf: String,
-        outf: String,
-        visout: String,
-        stderr: String,
-        elapsed: f64,
-        score_regex: &Regex,
+        files: CaseFiles,
+        run: &ProcessOutput,
+        vis: Option<Result<String, String>>,
+        score: Option<Score>,
         comment_regex: &Regex,
     ) -> Self {
-        let score = Self::parse_score(&visout, score_regex);
-        Self { case, inf, outf, visout, stderr, elapsed, score, comment_regex: comment_regex.clone() }
+        let evaluated = vis.is_some();
+        let (visout, vis_error) = match vis {
+            Some(Ok(visout)) => (visout, None),
+            Some(Err(msg)) => (String::new(), Some(msg)),
+            None => (String::new(), None),
+        };
+        let exit_code = run.status.and_then(|s| s.code());
+        let signal = run.status.and_then(exit_signal);
+        let stderr = String::from_utf8_lossy(&run.stderr).to_string();
+        let verdict = if run.timed_out {
+            Verdict::Tle
+        } else if !run.status.is_some_and(|s| s.success()) {
+            Verdict::Re { code: exit_code, signal }
+        } else if let Some(msg) = vis_error {
+            Verdict::VisError(msg)
+        } else if evaluated && score.is_none() {
+            Verdict::Wa
+        } else {
+            Verdict::Ok
+        };
+        Self {
+            case,
+            inf: files.inf,
+            outf: files.outf,
+            errf: files.errf,
+            visout,
+            panic_message: Self::extract_panic_message(&stderr),
+            comments: Self::lookup_comments_from(&stderr, comment_regex),
+            stderr,
+            elapsed: run.elapsed,
+            score,
+            verdict,
+            exit_code,
+            signal,
+            usage: run.usage,
+            cached: false,
+        }
+    }
+
+    /// 実行前後の入出力エラーで終わったケースの結果を作る。
+    pub fn io_error(case: u32, files: CaseFiles, err: &io::Error) -> Self {
+        Self {
+            case,
+            inf: files.inf,
+            outf: files.outf,
+            errf: files.errf,
+            visout: String::new(),
+            stderr: String::new(),
+            elapsed: 0.0,
+            score: None,
+            verdict: Verdict::IoError(err.to_string()),
+            exit_code: None,
+            signal: None,
+            panic_message: None,
+            usage: None,
+            comments: String::new(),
+            cached: false,
+        }
+    }
+
+    /// テスト用の、スコア `score` で成功したケースの結果。
+    #[cfg(test)]
+    pub(crate) fn ok_for_test(case: u32, score: i64) -> Self {
+        Self { verdict: Verdict::Ok, score: Some(Score::Int(score)), ..Self::failed_for_test(case) }
+    }
+
+    /// テスト用の、失敗したケースの結果。
+    #[cfg(test)]
+    pub(crate) fn failed_for_test(case: u32) -> Self {
+        Self::io_error(case, CaseFiles::default(), &io::Error::other("x"))
     }
 
     /// ビジュアライザ出力からスコアを抽出する。
-    pub fn parse_score(visout: &str, score_regex: &Regex) -> u64 {
+    /// スコアの行が無い、または数値としてパースできない場合は None。
+    pub fn parse_score(visout: &str, score_regex: &Regex, score_type: ScoreType) -> Option<Score> {
         for line in visout.lines() {
             if let Some(caps) = score_regex.captures(line) {
                 if let Some(m) = caps.get(1) {
-                    return m.as_str().parse().unwrap_or(0);
+                    return Score::parse(m.as_str(), score_type);
                 }
             }
         }
-        0
+        None
+    }
+
+    /// 成功したケースならスコアを返す。合計や比較にはこの値を使う。
+    pub fn ok_score(&self) -> Option<Score> {
+        self.score.filter(|_| self.verdict.is_ok())
+    }
+
+    /// stderr から Rust の panic メッセージを "メッセージ (場所)" の形で抽出する。
+    /// `panicked at src/main.rs:1:2:` の次行にメッセージが来る形式と、
+    /// 旧形式の `panicked at 'msg', src/main.rs:1:2` の両方に対応する。
+    pub fn extract_panic_message(stderr: &str) -> Option<String> {
+        const MARKER: &str = "panicked at ";
+        let mut lines = stderr.lines();
+        while let Some(line) = lines.next() {
+            let Some(pos) = line.find(MARKER) else {
+                continue;
+            };
+            let rest = &line[pos + MARKER.len()..];
+            if let Some((msg, loc)) = rest.strip_prefix('\'').and_then(|r| r.rsplit_once("', ")) {
+                return Some(format!("{} ({})", msg, loc));
+            }
+            let loc = rest.trim_end_matches(':');
+            let msg = lines.next().unwrap_or("").trim();
+            if msg.is_empty() {
+                return Some(loc.to_string());
+            }
+            return Some(format!("{} ({})", msg, loc));
+        }
+        None
     }
 
     pub fn lookup_comments(&self) -> String {
-        Self::lookup_comments_from(&self.stderr, &self.comment_regex)
+        self.comments.clone()
     }
 
     /// stderrの各行からコメントを抽出し、"/" で結合する。
@@ -181,60 +760,166 @@ impl CaseResult {
     }
 
     pub fn print(&self) {
+        self.print_with_baseline(None);
+    }
+
+    /// 結果を1行で表示する。ベースラインがあればスコアの差分と比率を併記する。
+    pub fn print_with_baseline(&self, baseline: Option<&Baseline>) {
+        println!("{}", self.format_with_baseline(baseline));
+    }
+
+    /// `print_with_baseline` で表示する行。端末に出力する場合は色を付ける。
+    pub fn format_with_baseline(&self, baseline: Option<&Baseline>) -> String {
+        let color = io::stdout().is_terminal();
         let cmts = self.lookup_comments();
-        println!(
-            "{:04} SCORE[{:>11}] ELAPSED[{:.2}s] CMTS[{}]",
+        let mut status = String::new();
+        if !self.verdict.is_ok() {
+            status.push_str(&format!(" {}", self.verdict));
+        }
+        if let Some(msg) = &self.panic_message {
+            status.push_str(&format!(" PANIC[{}]", msg));
+        }
+        let usage = match &self.usage {
+            Some(u) => format!(" CPU[{:.2}s] MEM[{:.1}MiB]", u.cpu_time(), u.max_rss_mib()),
+            None => String::new(),
+        };
+        let vs = match baseline {
+            Some(b) => match b.diff(self) {
+                Some(d) => {
+                    let text = format!(" VS[{}]", baseline::format_diff(&d));
+                    // 失敗したケースは行全体を赤にするので、ここでは色を付けない
+                    match d.ordering {
+                        Ordering::Greater if color && self.verdict.is_ok() => paint(&text, GREEN),
+                        Ordering::Less if color && self.verdict.is_ok() => paint(&text, RED),
+                        _ => text,
+                    }
+                }
+                None => " VS[-]".to_string(),
+            },
+            None => String::new(),
+        };
+        let cached = if self.cached { " CACHED" } else { "" };
+        let line = format!(
+            "{:04} SCORE[{:>11}]{} ELAPSED[{:.2}s]{}{}{} CMTS[{}]",
             self.case,
-            format_with_commas(self.score),
+            self.score.map_or("-".to_string(), format_with_commas),
+            vs,
             self.elapsed,
+            cached,
+            usage,
+            status,
             cmts
         );
+        // 失敗したケースは端末上では赤で強調する
+        if !self.verdict.is_ok() && color {
+            paint(&line, RED)
+        } else {
+            line
+        }
     }
 
     /// 出力ファイルの内容をクリップボードにコピーする。
     pub fn clip(&self) {
-        match fs::read_to_string(&self.outf) {
-            Ok(content) => {
-                if let Ok(mut clipboard) = arboard::Clipboard::new() {
-                    let _ = clipboard.set_text(content);
-                }
+        if let Ok(content) = fs::read_to_string(&self.outf) {
+            if let Ok(mut clipboard) = arboard::Clipboard::new() {
+                let _ = clipboard.set_text(content);
             }
-            Err(_) => {}
         }
     }
 }
 
-/// 数値を3桁区切りカンマ付き文字列に変換する (例: 12345 -> "12,345")。
-fn format_with_commas(n: u64) -> String {
+const RED: &str = "31";
+const GREEN: &str = "32";
+
+/// ANSI エスケープで文字列に色を付ける。
+fn paint(text: &str, color: &str) -> String {
+    format!("\x1b[{}m{}\x1b[0m", color, text)
+}
+
+#[cfg(unix)]
+fn exit_signal(status: std::process::ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal()
+}
+
+#[cfg(not(unix))]
+fn exit_signal(_status: std::process::ExitStatus) -> Option<i32> {
+    None
+}
+
+/// シグナル番号を名前に変換する (例: 11 -> "SIGSEGV")。
+fn signal_name(sig: i32) -> String {
+    let name = match sig {
+        1 => "SIGHUP",
+        2 => "SIGINT",
+        4 => "SIGILL",
+        6 => "SIGABRT",
+        7 => "SIGBUS",
+        8 => "SIGFPE",
+        9 => "SIGKILL",
+        11 => "SIGSEGV",
+        13 => "SIGPIPE",
+        15 => "SIGTERM",
+        _ => return format!("signal {}", sig),
+    };
+    name.to_string()
+}
+
+/// 数値の整数部を3桁区切りカンマ付きにする (例: 12345 -> "12,345", -1234.5 -> "-1,234.5")。
+pub(crate) fn format_with_commas<T: fmt::Display>(n: T) -> String {
     let s = n.to_string();
+    let (sign, rest) = match s.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", s.as_str()),
+    };
+    let (int_part, frac_part) = rest.split_at(rest.find('.').unwrap_or(rest.len()));
+    if int_part.is_empty() || !int_part.bytes().all(|b| b.is_ascii_digit()) {
+        return s;
+    }
     let mut result = String::new();
-    for (i, c) in s.chars().rev().enumerate() {
+    for (i, c) in int_part.chars().rev().enumerate() {
         if i > 0 && i % 3 == 0 {
             result.push(',');
         }
         result.push(c);
     }
-    result.chars().rev().collect()
+    let int_part: String = result.chars().rev().collect();
+    format!("{}{}{}", sign, int_part, frac_part)
 }
 
 impl Heu {
+    /// 設定が不正な場合は panic する。
     pub fn new(config: Config) -> Self {
-        let cases = parse_cases(
-            &config.test.cases.split_whitespace().map(String::from).collect::<Vec<_>>(),
-        );
-        let score_regex = Regex::new(&config.test.score_regex).unwrap_or_else(|e| {
-            panic!(
-                "Invalid test.score_regex '{}': {}",
-                config.test.score_regex, e
-            )
-        });
-        let comment_regex = Regex::new(&config.test.comment_regex).unwrap_or_else(|e| {
-            panic!(
-                "Invalid test.comment_regex '{}': {}",
-                config.test.comment_regex, e
-            )
-        });
-        Self { config, cases, score_regex, comment_regex }
+        Self::try_new(config).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// ケース指定と正規表現を解釈して Heu を作る。
+    /// ケース指定は集合や実行履歴も参照して、ここでケース番号の列に解決する。
+    pub fn try_new(config: Config) -> io::Result<Self> {
+        let invalid = |key: &str, value: &str, e: &dyn fmt::Display| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid test.{} '{}': {}", key, value, e))
+        };
+        let cases = cases::CaseSpec::parse(&config.test.cases)
+            .and_then(|spec| spec.resolve(Some(&config)))
+            .map_err(|e| invalid("cases", &config.test.cases, &e))?;
+        let score_regex = Regex::new(&config.test.score_regex)
+            .map_err(|e| invalid("score_regex", &config.test.score_regex, &e))?;
+        let comment_regex = Regex::new(&config.test.comment_regex)
+            .map_err(|e| invalid("comment_regex", &config.test.comment_regex, &e))?;
+        Ok(Self { config, cases, score_regex, comment_regex, command_hash: Mutex::new(None), watching: AtomicBool::new(false) })
+    }
+
+    /// 設定とケースを差し替えた Heu。正規表現とウォッチモードかどうかは引き継ぎ、
+    /// 実行コマンドのハッシュは計算し直す。`config` の正規表現は変えないこと。
+    pub(crate) fn with_config_and_cases(&self, config: Config, cases: Vec<u32>) -> Heu {
+        Heu {
+            config,
+            cases,
+            score_regex: self.score_regex.clone(),
+            comment_regex: self.comment_regex.clone(),
+            command_hash: Mutex::new(None),
+            watching: AtomicBool::new(self.watching.load(AtomicOrdering::SeqCst)),
+        }
     }
 
     pub fn input_file(&self, case: u32) -> String {
@@ -245,106 +930,228 @@ impl Heu {
         format!("{}/{:04}.txt", self.config.test.out_dir, case)
     }
 
+    /// ソリューションの stderr を保存するファイル。
+    pub fn stderr_file(&self, case: u32) -> String {
+        format!("{}/stderr/{:04}.txt", self.config.test.out_dir, case)
+    }
+
+    pub fn case_files(&self, case: u32) -> CaseFiles {
+        CaseFiles { inf: self.input_file(case), outf: self.output_file(case), errf: self.stderr_file(case) }
+    }
+
     /// ビルドコマンドを実行する。enable が false の場合はスキップ。
     pub fn build(&self) -> io::Result<()> {
         if !self.config.build.enable {
             return Ok(());
         }
-        let status = Self::command_from_str(&self.config.build.command)?.status()?;
-        if !status.success() {
-            return Err(io::Error::new(io::ErrorKind::Other, "build failed"));
+        if !self.run_build(&self.config.build.command)? {
+            return Err(io::Error::other("build failed"));
         }
         Ok(())
     }
 
-    /// ビルド後、全ケースを並列実行してスコアを表示する。
-    pub fn execute(&self) -> io::Result<()> {
-        self.build()?;
-
-        if self.config.test.no_evaluate {
-            return self.execute_run_only();
+    /// ビルドコマンドを実行し、成功したかを返す。
+    /// 通常は端末の入出力をそのまま使う (Ctrl-C は同じプロセスグループのビルドにも届く)。
+    /// ウォッチモードではビルド中の変更で kill できるよう [`process::run`] で実行し、
+    /// 出力は終わってからまとめて表示する。中断された場合は `Interrupted` のエラーを返す。
+    pub(crate) fn run_build(&self, command: &str) -> io::Result<bool> {
+        let mut cmd = Self::command_from_str(command)?;
+        if !self.watching.load(AtomicOrdering::SeqCst) {
+            return Ok(cmd.status()?.success());
         }
+        cmd.stdin(std::process::Stdio::null());
+        let output = process::run(&mut cmd, None, None)?;
+        io::stdout().write_all(&output.stdout)?;
+        io::stderr().write_all(&output.stderr)?;
+        Ok(output.status.is_some_and(|s| s.success()))
+    }
 
-        self.execute_multiprocess()
+    /// 入力ファイルを `test.gen` で生成する。force でなければ既に存在するケースは生成しない。
+    /// 生成したケース数を返す。
+    pub fn generate_inputs(&self, force: bool) -> io::Result<usize> {
+        let gen = self.config.test.gen.as_deref().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "test.gen is not set in config")
+        })?;
+        let cases: Vec<u32> = self
+            .cases
+            .iter()
+            .copied()
+            .filter(|&c| force || !std::path::Path::new(&self.input_file(c)).exists())
+            .collect();
+        if cases.is_empty() {
+            return Ok(0);
+        }
+        let seeds = gen::Seeds::load(self.config.test.seeds.as_deref())?;
+        fs::create_dir_all(&self.config.test.in_dir)?;
+        gen::generate(gen, &seeds, &self.config.test.in_dir, &cases, self.config.test.threads)?;
+        Ok(cases.len())
     }
 
-    /// ビジュアライザによる評価なしでソリューションを実行する。
-    fn execute_run_only(&self) -> io::Result<()> {
-        for &case in &self.cases {
-            let inf = self.input_file(case);
-            let status = Self::command_from_str(&self.config.test.bin)?
-                .env("INPUT_FILE", &inf)
-                .env("IN_FILE", &inf)
-                .stdin(std::process::Stdio::from(fs::File::open(&inf)?))
-                .status()?;
-            if !status.success() {
-                return Err(io::Error::new(io::ErrorKind::Other, format!("case {} failed", case)));
+    /// ビルドし、`test.gen` があれば入力ファイルが無いケースを生成する。
+    fn prepare(&self) -> io::Result<()> {
+        self.build()?;
+        *self.command_hash.lock().unwrap() = None;
+        if self.config.test.gen.is_some() {
+            let generated = self.generate_inputs(false)?;
+            if generated > 0 {
+                eprintln!("Generated {} inputs", generated);
             }
         }
         Ok(())
     }
 
+    /// ビルド後、全ケースを並列実行してスコアを表示し、実行履歴に保存する。
+    /// `test.gen` があれば、入力ファイルが無いケースを先に生成する。
+    /// no_evaluate の場合はビジュアライザによる評価を省き、履歴にも保存しない。
+    /// 実行全体の集計を返す。Ctrl-C で中断した場合は終わったケースの集計を表示して履歴に保存し、
+    /// `Interrupted` のエラーを返す。
+    pub fn execute(&self) -> io::Result<Summary> {
+        self.prepare()?;
+        self.run_and_save(Vec::new(), None)
+    }
+
+    /// ビルド済みとして全ケースを実行し、実行履歴に保存する。
+    /// `reused` は過去の実行から引き継ぐケースの結果で、今回の結果と合わせて1つの実行記録にする。
+    /// `reused_from` はその実行の ID。
+    fn run_and_save(&self, reused: Vec<CaseResult>, reused_from: Option<&str>) -> io::Result<Summary> {
+        if self.config.test.no_evaluate {
+            let (_, summary) = self.execute_multiprocess(None, &BestScores::default())?;
+            process::check_cancelled()?;
+            return Ok(summary);
+        }
+
+        let out_dir = &self.config.test.out_dir;
+        let objective = self.config.test.objective;
+        let baseline = match &self.config.test.baseline {
+            Some(spec) => {
+                let b = Baseline::resolve(out_dir, spec, &self.cases, objective)?;
+                eprintln!("Baseline: {}", b.id);
+                Some(b)
+            }
+            None => None,
+        };
+        if self.config.sequential.enable && baseline.is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "sequential mode needs a baseline: pass -b or set test.baseline",
+            ));
+        }
+        let mut best = BestScores::load(out_dir)?;
+
+        let (mut results, mut summary) = self.execute_multiprocess(baseline.as_ref(), &best)?;
+        let pending: Vec<u32> =
+            self.cases.iter().copied().filter(|c| !results.iter().any(|r| r.case == *c)).collect();
+        if let Some(id) = reused_from {
+            // 今回実行し直したケースは数えない
+            let reused_cases = reused.iter().filter(|r| !results.iter().any(|n| n.case == r.case)).count();
+            results = resume::merge_results(reused, results);
+            summary = Summary::new(&results, &best, objective, self.config.test.score_type);
+            let line = format!(
+                "MERGED[{} reused from {}] TOTAL={} AVG={} REL={} FAILED={}/{}",
+                reused_cases,
+                id,
+                format_with_commas(summary.total),
+                format_stat(summary.mean),
+                format_with_commas(summary.relative_total),
+                summary.failed.len(),
+                summary.cases
+            );
+            if self.config.test.format.is_human() {
+                println!("{}", line);
+            } else {
+                eprintln!("{}", line);
+            }
+        }
+        let mut record = history::RunRecord::new(&self.config, results);
+        record.bin_hash = hash::command_hash(&self.config.test.bin).ok();
+        record.resumed_from = reused_from.map(String::from);
+        // 中断した場合も終わったケースは保存し、残りのケースを記録しておく
+        if process::is_interrupted() {
+            record.interrupted = true;
+            record.pending = pending;
+        }
+        let path = history::save(out_dir, &mut record)?;
+        eprintln!("Saved run {}: {}", record.id, path.display());
+
+        let updated = best.update(&record.cases, Some(&record.id), objective);
+        if updated > 0 {
+            best.save(out_dir)?;
+            eprintln!("Updated best scores: {} cases", updated);
+        }
+        process::check_cancelled()?;
+        Ok(summary)
+    }
+
     /// 1ケースを実行し、ビジュアライザで評価して結果を返す。
-    fn execute_case(&self, case: u32) -> io::Result<CaseResult> {
-        let inf = self.input_file(case);
-        let outf = self.output_file(case);
+    /// 失敗した場合もエラーを返さず、判定結果として記録する。
+    fn execute_case(&self, case: u32, env: &[(String, String)]) -> CaseResult {
+        let files = self.case_files(case);
+        match self.try_execute_case(case, &files, env) {
+            Ok(result) => result,
+            Err(e) => CaseResult::io_error(case, files, &e),
+        }
+    }
 
-        if let Some(parent) = std::path::Path::new(&outf).parent() {
-            fs::create_dir_all(parent)?;
+    fn try_execute_case(&self, case: u32, files: &CaseFiles, env: &[(String, String)]) -> io::Result<CaseResult> {
+        for path in [&files.outf, &files.errf] {
+            if let Some(parent) = std::path::Path::new(path).parent() {
+                fs::create_dir_all(parent)?;
+            }
         }
 
-        let input_data = fs::read(&inf)?;
+        let input_data = fs::read(&files.inf).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => {
+                io::Error::new(e.kind(), format!("input not found: {} (set test.gen to generate it)", files.inf))
+            }
+            _ => e,
+        })?;
 
-        let start = Instant::now();
-        let (stdout, stderr_bytes) = self.run_command(&inf, &input_data)?;
-        let elapsed = start.elapsed().as_secs_f64();
-        fs::write(&outf, &stdout)?;
-        let stderr = String::from_utf8_lossy(&stderr_bytes).to_string();
+        let cache_key = self.cache_key(&input_data, env);
+        if let Some(result) = cache_key.as_ref().and_then(|key| self.load_cached(key, case, files)) {
+            return Ok(result);
+        }
 
-        let visout = self.exe_vis(&inf, &outf)?;
+        // TLE の場合も途中までの出力を保存して評価する
+        let run = self.run_command(&files.inf, &input_data, env)?;
+        fs::write(&files.outf, &run.stdout)?;
+        fs::write(&files.errf, &run.stderr)?;
 
-        Ok(CaseResult::new(
-            case,
-            inf,
-            outf,
-            visout,
-            stderr,
-            elapsed,
-            &self.score_regex,
-            &self.comment_regex,
-        ))
+        let vis = if self.config.test.no_evaluate {
+            None
+        } else {
+            Some(self.exe_vis(&files.inf, &files.outf))
+        };
+        let score = vis
+            .as_ref()
+            .and_then(|v| v.as_ref().ok())
+            .and_then(|v| CaseResult::parse_score(v, &self.score_regex, self.config.test.score_type));
+
+        let result = CaseResult::new(case, files.clone(), &run, vis, score, &self.comment_regex);
+        if let Some(key) = cache_key.filter(|_| result.verdict.is_ok()) {
+            if let Err(e) = self.store_cached(&key, &result, &run) {
+                eprintln!("Warning: failed to cache case {}: {}", case, e);
+            }
+        }
+        Ok(result)
     }
 
-    /// ソリューション(またはtester経由)を実行し、stdout/stderr(bytes) を返す。
-    fn run_command(&self, inf: &str, input_data: &[u8]) -> io::Result<(Vec<u8>, Vec<u8>)> {
-        let output = if self.config.test.use_tester {
+    /// ソリューション(またはtester経由)を実行する。`env` は INPUT_FILE と合わせて渡す環境変数。
+    /// `test.time_limit` を超えた場合は tester ごと kill し、途中までの出力を返す。
+    fn run_command(&self, inf: &str, input_data: &[u8], env: &[(String, String)]) -> io::Result<ProcessOutput> {
+        let time_limit = self.config.test.time_limit.map(Duration::from_secs_f64);
+        if self.config.test.use_tester {
             let mut cmd = Self::command_from_str(&self.config.test.tester)?;
             cmd.args(Self::parse_command_parts(&self.config.test.bin)?)
                 .env("INPUT_FILE", inf)
                 .env("IN_FILE", inf)
-                .stdin(std::process::Stdio::from(fs::File::open(inf)?))
-                .stdout(std::process::Stdio::piped())
-                .stderr(std::process::Stdio::piped());
-            cmd.output()?
+                .envs(env.iter().map(|(k, v)| (k, v)))
+                .stdin(std::process::Stdio::from(fs::File::open(inf)?));
+            process::run(&mut cmd, None, time_limit)
         } else {
-            Self::command_from_str(&self.config.test.bin)?
-                .env("INPUT_FILE", inf)
-                .env("IN_FILE", inf)
-                .stdin(std::process::Stdio::piped())
-                .stdout(std::process::Stdio::piped())
-                .stderr(std::process::Stdio::piped())
-                .spawn()
-                .and_then(|mut child| {
-                    use std::io::Write;
-                    if let Some(stdin) = child.stdin.as_mut() {
-                        stdin.write_all(input_data)?;
-                    }
-                    drop(child.stdin.take());
-                    child.wait_with_output()
-                })?
-        };
-
-        Ok((output.stdout, output.stderr))
+            let mut cmd = Self::command_from_str(&self.config.test.bin)?;
+            cmd.env("INPUT_FILE", inf).env("IN_FILE", inf).envs(env.iter().map(|(k, v)| (k, v)));
+            process::run(&mut cmd, Some(input_data), time_limit)
+        }
     }
 
     fn parse_command_parts(cmd: &str) -> io::Result<Vec<String>> {
@@ -358,7 +1165,7 @@ impl Heu {
     }
 
     /// コマンド文字列を分割して、シェルを介さず実行用 Command を作る。
-    fn command_from_str(cmd: &str) -> io::Result<Command> {
+    pub(crate) fn command_from_str(cmd: &str) -> io::Result<Command> {
         let mut parts = Self::parse_command_parts(cmd)?;
         let program = parts.remove(0);
         let mut command = Command::new(program);
@@ -367,90 +1174,314 @@ impl Heu {
     }
 
     /// ビジュアライザコマンドを実行してスコア出力を返す。
-    fn exe_vis(&self, inf: &str, outf: &str) -> io::Result<String> {
-        let output = Self::command_from_str(&self.config.test.vis)?
-            .arg(inf)
-            .arg(outf)
-            .output()?;
-        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    /// 起動できなかった場合や、スコアを出さずに異常終了した場合は理由を返す。
+    fn exe_vis(&self, inf: &str, outf: &str) -> Result<String, String> {
+        let output = Self::command_from_str(&self.config.test.vis)
+            .and_then(|mut cmd| cmd.arg(inf).arg(outf).output())
+            .map_err(|e| e.to_string())?;
+        let visout = String::from_utf8_lossy(&output.stdout).to_string();
+        if !output.status.success() && !visout.lines().any(|l| self.score_regex.is_match(l)) {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let msg = stderr.lines().chain(visout.lines()).find(|l| !l.trim().is_empty());
+            return Err(msg.unwrap_or("visualizer failed").trim().to_string());
+        }
+        Ok(visout)
     }
 
-    /// 全ケースを並列実行し、ケース番号昇順で結果を即時出力する。
-    fn execute_multiprocess(&self) -> io::Result<()> {
+    /// 全ケースを並列実行し、ケース番号昇順で結果を即時出力する。最後に集計を表示して返す。
+    /// `[sequential]` が有効でベースラインがあれば、ケースを `batch` 件ずつ実行し、
+    /// ベースラインとの差が決まった時点で残りのケースを実行せずに打ち切る。
+    fn execute_multiprocess(
+        &self,
+        baseline: Option<&Baseline>,
+        best: &BestScores,
+    ) -> io::Result<(Vec<CaseResult>, Summary)> {
+        let objective = self.config.test.objective;
         let n = self.cases.len();
-        let (tx, rx) = mpsc::channel::<(usize, io::Result<CaseResult>)>();
-
-        let run = |tx: mpsc::Sender<_>| {
-            self.cases
-                .par_iter()
-                .enumerate()
-                .for_each(|(i, &case)| {
-                    let result = self.execute_case(case);
-                    let _ = tx.send((i, result));
-                });
+        let mut results: Vec<CaseResult> = Vec::with_capacity(n);
+        let mut win_loss = WinLoss::default();
+        // ケースごとの相対スコアの差 (今回 - ベースライン)
+        let mut diffs = Vec::new();
+        let format = self.config.test.format;
+        format.print_header();
+
+        let sequential = match baseline {
+            Some(_) if self.config.sequential.enable => Some(SequentialTest::new(&self.config.sequential, n)),
+            _ => None,
         };
-
-        let recv_result = std::thread::scope(|s| {
-            let receiver = s.spawn(|| -> io::Result<()> {
-                let mut buf: Vec<Option<CaseResult>> = (0..n).map(|_| None).collect();
-                let mut next = 0;
-                let mut total: u64 = 0;
-                let mut last: Option<CaseResult> = None;
-
-                for (i, result) in rx {
-                    buf[i] = Some(result?);
-                    while next < n {
-                        if let Some(r) = buf[next].take() {
-                            r.print();
-                            total += r.score;
-                            last = Some(r);
-                            next += 1;
-                        } else {
-                            break;
+        let batch = sequential.as_ref().map_or(n, |t| t.batch).max(1);
+        let mut outcome = None;
+        // パイプへの出力では使わない
+        let dashboard = (self.config.test.tui && format.is_human() && io::stdout().is_terminal()).then(|| {
+            Dashboard::new(n, self.config.test.threads, self.config.test.score_type, objective, best)
+        });
+        let dashboard = dashboard.as_ref();
+        let mut run_batches = || -> io::Result<()> {
+            for (i, cases) in self.cases.chunks(batch).enumerate() {
+                let run_case = |&case: &u32| {
+                    if let Some(d) = dashboard {
+                        d.start(case);
+                    }
+                    let r = self.execute_case(case, &[]);
+                    if let Some(d) = dashboard {
+                        d.finish(&r);
+                    }
+                    r
+                };
+                run_ordered(self.config.test.threads, cases, run_case, |r| {
+                    if format.is_human() {
+                        let line = r.format_with_baseline(baseline);
+                        match dashboard {
+                            Some(d) => d.println(&line),
+                            None => println!("{}", line),
                         }
+                    } else {
+                        format.print_case(&r);
+                    }
+                    if let Some(d) = baseline.and_then(|b| b.diff(&r)) {
+                        win_loss.add(&d);
+                        diffs.extend(d.relative_diff(objective));
                     }
+                    results.push(r);
+                })?;
+                let Some(test) = &sequential else { continue };
+                let decision = test.decide(&diffs);
+                let msg = format!(
+                    "Batch {}: {}/{} cases P={} (stops below {:.4}) {}",
+                    i + 1,
+                    results.len(),
+                    n,
+                    stats::wilcoxon_signed_rank(&diffs).map_or("-".to_string(), |w| format!("{:.4}", w.p_value)),
+                    test.alpha(),
+                    decision
+                );
+                // ダッシュボードの表示を崩さないよう同じ出力に流す
+                match dashboard {
+                    Some(d) => d.println(&msg),
+                    None => eprintln!("{}", msg),
                 }
-
-                if let Some(r) = last {
-                    r.clip();
+                outcome = Some(SequentialResult { decision, cases: results.len(), budget: n, batches: i + 1 });
+                if decision != Decision::Undecided {
+                    break;
                 }
-                println!("TOTAL={}", format_with_commas(total));
-                Ok(())
-            });
-
-            let pool = rayon::ThreadPoolBuilder::new()
-                .num_threads(self.config.test.threads)
-                .build()
-                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-            pool.install(|| run(tx));
-
-            receiver.join().unwrap()
+            }
+            Ok(())
+        };
+        let result = std::thread::scope(|s| {
+            if let Some(d) = dashboard {
+                s.spawn(|| d.run());
+            }
+            let result = run_batches();
+            if let Some(d) = dashboard {
+                d.close();
+            }
+            result
         });
+        // Ctrl-C で中断した場合は終わったケースだけで集計する
+        let interrupted = match result {
+            Err(e) if e.kind() == io::ErrorKind::Interrupted && process::is_interrupted() => true,
+            result => {
+                result?;
+                false
+            }
+        };
+        let interrupted_line = format!("INTERRUPTED: {}/{} cases completed", results.len(), n);
 
-        recv_result
+        if let Some(r) = results.last() {
+            r.clip();
+        }
+        // 失敗したケースはスコアの集計に含めない
+        let mut summary = Summary::new(&results, best, objective, self.config.test.score_type);
+        if baseline.is_some() {
+            summary.significance = stats::Significance::from_diffs(&diffs);
+        }
+        if !format.is_human() {
+            format.print_summary(&results, &summary);
+            if let (Some(test), Some(outcome)) = (&sequential, &outcome) {
+                eprintln!("{}", outcome.format(test));
+            }
+            if interrupted {
+                eprintln!("{}", interrupted_line);
+            }
+            return Ok((results, summary));
+        }
+        let mut total_line = format!(
+            "TOTAL={} AVG={} REL={}",
+            format_with_commas(summary.total),
+            format_stat(summary.mean),
+            format_with_commas(summary.relative_total)
+        );
+        if let Some(b) = baseline {
+            // 失敗したケースを 0 点として足すと最小化で良く見えるので、両方で成功したケースだけを比べる
+            let t = b.total_diff(&results);
+            total_line.push_str(&format!(
+                " VS[{}] over {}/{} cases WIN={} LOSE={} DRAW={}",
+                baseline::format_diff(&t.diff),
+                t.compared,
+                t.cases,
+                win_loss.win,
+                win_loss.lose,
+                win_loss.draw
+            ));
+        }
+        // ベースラインとの差が偶然の範囲かどうか
+        if let Some(sig) = &summary.significance {
+            let verdict = if sig.is_significant() { "SIGNIFICANT" } else { "NOT_SIGNIFICANT" };
+            let verdict = match sig.mean_diff.partial_cmp(&0.0) {
+                Some(Ordering::Greater) if sig.is_significant() && io::stdout().is_terminal() => paint(verdict, GREEN),
+                Some(Ordering::Less) if sig.is_significant() && io::stdout().is_terminal() => paint(verdict, RED),
+                _ => verdict.to_string(),
+            };
+            total_line.push_str(&format!(" {} {}", sig.format(), verdict));
+        }
+        // 評価なしの場合はスコアが無いので合計や統計量を表示しない
+        if !self.config.test.no_evaluate {
+            println!("{}", total_line);
+            print_score_stats(&summary);
+        }
+        if !summary.failed.is_empty() {
+            let ids: Vec<String> = summary.failed.iter().map(|c| format!("{:04}", c)).collect();
+            println!("FAILED={}/{} [{}]", summary.failed.len(), results.len(), ids.join(" "));
+        }
+        print_resource_summary(&results, &summary);
+        if let (Some(test), Some(outcome)) = (&sequential, &outcome) {
+            let line = outcome.format(test);
+            match outcome.decision {
+                Decision::Improved if io::stdout().is_terminal() => println!("{}", paint(&line, GREEN)),
+                Decision::Regressed if io::stdout().is_terminal() => println!("{}", paint(&line, RED)),
+                _ => println!("{}", line),
+            }
+        }
+        if interrupted {
+            if io::stdout().is_terminal() {
+                println!("{}", paint(&interrupted_line, RED));
+            } else {
+                println!("{}", interrupted_line);
+            }
+        }
+        Ok((results, summary))
     }
-}
 
-/// ケース指定文字列をパースする。"3-5" はレンジ、"3" は単一ケース。空なら 0-4。
-pub fn parse_cases(args: &[String]) -> Vec<u32> {
-    if args.is_empty() {
-        return (0..5).collect();
+    /// `cases` を `test.threads` 並列で実行し、`cases` の順に `on_result` を呼ぶ。
+    /// `env` はソリューションに追加で渡す環境変数。
+    fn run_parallel<F>(&self, cases: &[u32], env: &[(String, String)], on_result: F) -> io::Result<()>
+    where
+        F: FnMut(CaseResult) + Send,
+    {
+        run_ordered(self.config.test.threads, cases, |&case| self.execute_case(case, env), on_result)
     }
+}
 
-    let mut ret = Vec::new();
-    for s in args {
-        let parts: Vec<&str> = s.split('-').collect();
-        if parts.len() == 1 {
-            if let Ok(n) = parts[0].parse::<u32>() {
-                ret.push(n);
+/// `jobs` を `threads` 並列で実行し、`jobs` の順に `on_result` を呼ぶ。
+/// 途中で中断された場合は新しいジョブを始めず、終わっていたジョブの結果を順に渡してから
+/// `Interrupted` のエラーを返す。このときは間が抜けるので、結果がどのジョブのものかは結果自体に持たせる。
+fn run_ordered<T, O, R, G>(threads: usize, jobs: &[T], run_job: R, mut on_result: G) -> io::Result<()>
+where
+    T: Sync,
+    O: Send,
+    R: Fn(&T) -> O + Sync,
+    G: FnMut(O) + Send,
+{
+    let n = jobs.len();
+    let (tx, rx) = mpsc::channel::<(usize, O)>();
+
+    let run = |tx: mpsc::Sender<_>| {
+        jobs.par_iter().enumerate().for_each(|(i, job)| {
+            if process::is_cancelled() {
+                return;
             }
-        } else if parts.len() == 2 {
-            if let (Ok(start), Ok(end)) = (parts[0].parse::<u32>(), parts[1].parse::<u32>()) {
-                ret.extend(start..=end);
+            let result = run_job(job);
+            // 中断で kill されたケースの結果は使わない
+            if !process::is_cancelled() {
+                let _ = tx.send((i, result));
             }
-        }
+        });
+    };
+
+    std::thread::scope(|s| {
+        let receiver = s.spawn(move || {
+            let mut buf: Vec<Option<O>> = (0..n).map(|_| None).collect();
+            let mut next = 0;
+            for (i, result) in rx {
+                buf[i] = Some(result);
+                while next < n {
+                    if let Some(r) = buf[next].take() {
+                        on_result(r);
+                        next += 1;
+                    } else {
+                        break;
+                    }
+                }
+            }
+            // 中断された場合は間が抜けているので、終わっていたものだけを渡す
+            buf.into_iter().flatten().for_each(on_result);
+        });
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .map_err(io::Error::other)?;
+        pool.install(|| run(tx));
+
+        receiver.join().unwrap();
+        process::check_cancelled()
+    })
+}
+
+/// 小数の統計量を表示用にする。値が無ければ "-"。
+fn format_stat(value: Option<f64>) -> String {
+    value.map_or("-".to_string(), |v| format_with_commas(format!("{:.2}", v)))
+}
+
+/// 成功したケースのスコアの統計量を表示する。
+fn print_score_stats(summary: &Summary) {
+    let case_score = |cs: Option<stats::CaseScore>| {
+        cs.map_or("-".to_string(), |cs| format!("{}({:04})", format_with_commas(cs.score), cs.case))
+    };
+    println!(
+        "MEDIAN={} STDDEV={} MIN={} MAX={} GMEAN={} LOGSUM={}",
+        format_stat(summary.median),
+        format_stat(summary.stddev),
+        case_score(summary.min),
+        case_score(summary.max),
+        format_stat(summary.geometric_mean),
+        summary.log_score_sum.map_or("-".to_string(), |v| format!("{:.4}", v))
+    );
+}
+
+/// 全ケースの実行時間・CPU 時間・ピークメモリの最大値と平均値を表示する。
+fn print_resource_summary(results: &[CaseResult], summary: &Summary) {
+    fn max_avg(values: &[f64]) -> (f64, f64) {
+        let max = values.iter().copied().fold(0.0, f64::max);
+        (max, values.iter().sum::<f64>() / values.len() as f64)
+    }
+
+    if results.is_empty() {
+        return;
     }
-    ret
+    let mut line = format!(
+        "ELAPSED[max {:.2}s p95 {:.2}s avg {:.2}s]",
+        summary.max_elapsed, summary.p95_elapsed, summary.mean_elapsed
+    );
+
+    let usages: Vec<&ResourceUsage> = results.iter().filter_map(|r| r.usage.as_ref()).collect();
+    if !usages.is_empty() {
+        let cpu: Vec<f64> = usages.iter().map(|u| u.cpu_time()).collect();
+        let mem: Vec<f64> = usages.iter().map(|u| u.max_rss_mib()).collect();
+        let (max_cpu, avg_cpu) = max_avg(&cpu);
+        let (max_mem, avg_mem) = max_avg(&mem);
+        line.push_str(&format!(
+            " CPU[max {:.2}s avg {:.2}s] MEM[max {:.1}MiB avg {:.1}MiB]",
+            max_cpu, avg_cpu, max_mem, avg_mem
+        ));
+    }
+    println!("{}", line);
+}
+
+/// ケース指定をパースする。"3-5" はレンジ、"3" は単一ケース、"^3" は除外、"0-9:3" は間隔指定。空なら 0-4。
+/// 集合や履歴を参照する指定は使えない (`Heu::try_new` で設定と合わせて解決する)。
+pub fn parse_cases(args: &[String]) -> io::Result<Vec<u32>> {
+    cases::CaseSpec::parse(&args.join(" "))?.resolve(None)
 }
 
 #[cfg(test)]
@@ -474,64 +1505,177 @@ mod tests {
                 vis: String::new(),
                 tester: String::new(),
                 score_regex: "Score = (\\d+)".to_string(),
+                score_type: ScoreType::Int,
                 comment_regex: "^# (.*)$".to_string(),
+                time_limit: None,
+                baseline: None,
+                objective: Objective::Max,
+                gen: None,
+                seeds: None,
+                format: OutputFormat::Human,
+                tui: false,
+                cache: false,
+                cache_env: Vec::new(),
             },
+            sets: BTreeMap::new(),
+            sweep: SweepConfig::default(),
+            tune: TuneConfig::default(),
+            profiles: BTreeMap::new(),
+            sequential: SequentialConfig::default(),
+            watch: WatchConfig::default(),
         }
     }
 
     #[test]
     fn test_parse_cases_empty() {
         let args: Vec<String> = vec![];
-        assert_eq!(parse_cases(&args), vec![0, 1, 2, 3, 4]);
+        assert_eq!(parse_cases(&args).unwrap(), vec![0, 1, 2, 3, 4]);
     }
 
     #[test]
     fn test_parse_cases_single() {
         let args = vec!["3".to_string()];
-        assert_eq!(parse_cases(&args), vec![3]);
+        assert_eq!(parse_cases(&args).unwrap(), vec![3]);
     }
 
     #[test]
     fn test_parse_cases_multiple() {
         let args = vec!["0".to_string(), "1".to_string(), "3".to_string()];
-        assert_eq!(parse_cases(&args), vec![0, 1, 3]);
+        assert_eq!(parse_cases(&args).unwrap(), vec![0, 1, 3]);
     }
 
     #[test]
     fn test_parse_cases_range() {
         let args = vec!["3-5".to_string()];
-        assert_eq!(parse_cases(&args), vec![3, 4, 5]);
+        assert_eq!(parse_cases(&args).unwrap(), vec![3, 4, 5]);
     }
 
     #[test]
     fn test_parse_cases_mixed() {
         let args = vec!["0".to_string(), "1".to_string(), "3-5".to_string()];
-        assert_eq!(parse_cases(&args), vec![0, 1, 3, 4, 5]);
+        assert_eq!(parse_cases(&args).unwrap(), vec![0, 1, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_parse_cases_invalid() {
+        for arg in ["3-", "a", "5-3"] {
+            assert!(parse_cases(&[arg.to_string()]).is_err(), "{}", arg);
+        }
     }
 
     #[test]
     fn test_parse_score_normal() {
         let re = Regex::new(r"Score = (\d+)").unwrap();
-        assert_eq!(CaseResult::parse_score("Score = 12345", &re), 12345);
+        assert_eq!(CaseResult::parse_score("Score = 12345", &re, ScoreType::Int), Some(Score::Int(12345)));
     }
 
     #[test]
     fn test_parse_score_multiline() {
         let re = Regex::new(r"Score = (\d+)").unwrap();
         let visout = "some info\nScore = 67890\nother info";
-        assert_eq!(CaseResult::parse_score(visout, &re), 67890);
+        assert_eq!(CaseResult::parse_score(visout, &re, ScoreType::Int), Some(Score::Int(67890)));
     }
 
     #[test]
     fn test_parse_score_none() {
         let re = Regex::new(r"Score = (\d+)").unwrap();
-        assert_eq!(CaseResult::parse_score("no score here", &re), 0);
+        assert_eq!(CaseResult::parse_score("no score here", &re, ScoreType::Int), None);
     }
 
     #[test]
     fn test_parse_score_custom_regex() {
         let re = Regex::new(r"TotalScore: (\d+)").unwrap();
-        assert_eq!(CaseResult::parse_score("TotalScore: 42", &re), 42);
+        assert_eq!(CaseResult::parse_score("TotalScore: 42", &re, ScoreType::Int), Some(Score::Int(42)));
+    }
+
+    #[test]
+    fn test_parse_score_negative_and_float() {
+        let re = Regex::new(r"Score = (\S+)").unwrap();
+        assert_eq!(CaseResult::parse_score("Score = -15", &re, ScoreType::Int), Some(Score::Int(-15)));
+        assert_eq!(CaseResult::parse_score("Score = 0.125", &re, ScoreType::Float), Some(Score::Float(0.125)));
+        assert_eq!(CaseResult::parse_score("Score = 0.125", &re, ScoreType::Int), None);
+    }
+
+    #[test]
+    fn test_format_with_commas() {
+        assert_eq!(format_with_commas(0u64), "0");
+        assert_eq!(format_with_commas(1234567u64), "1,234,567");
+        assert_eq!(format_with_commas(-1234i64), "-1,234");
+        assert_eq!(format_with_commas(Score::Float(-12345.25)), "-12,345.25");
+        assert_eq!(format_with_commas(f64::INFINITY), "inf");
+    }
+
+    #[cfg(unix)]
+    fn process_output(raw_status: i32, timed_out: bool) -> ProcessOutput {
+        use std::os::unix::process::ExitStatusExt;
+        ProcessOutput {
+            stdout: Vec::new(),
+            stderr: b"# cmt\n".to_vec(),
+            status: Some(std::process::ExitStatus::from_raw(raw_status)),
+            elapsed: 0.5,
+            timed_out,
+            usage: None,
+        }
+    }
+
+    #[cfg(unix)]
+    fn verdict_of(run: &ProcessOutput, vis: Result<String, String>) -> Verdict {
+        let score_re = Regex::new(r"Score = (\d+)").unwrap();
+        let cmt_re = Regex::new(r"^# (.*)$").unwrap();
+        let score = vis.as_ref().ok().and_then(|v| CaseResult::parse_score(v, &score_re, ScoreType::Int));
+        CaseResult::new(0, CaseFiles::default(), run, Some(vis), score, &cmt_re).verdict
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_verdict_ok_and_wa() {
+        let run = process_output(0, false);
+        assert_eq!(verdict_of(&run, Ok("Score = 10".to_string())), Verdict::Ok);
+        assert_eq!(verdict_of(&run, Ok("invalid output".to_string())), Verdict::Wa);
+        // 評価なしの場合はスコアが無くても OK
+        let cmt_re = Regex::new(r"^# (.*)$").unwrap();
+        let r = CaseResult::new(0, CaseFiles::default(), &run, None, None, &cmt_re);
+        assert_eq!(r.verdict, Verdict::Ok);
+        assert_eq!(r.comments, "cmt");
+        assert_eq!(
+            verdict_of(&run, Err("not found".to_string())),
+            Verdict::VisError("not found".to_string())
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_verdict_re_and_tle() {
+        // 終了コード 101 (wait ステータスでは上位バイト)
+        let run = process_output(101 << 8, false);
+        assert_eq!(
+            verdict_of(&run, Ok("Score = 10".to_string())),
+            Verdict::Re { code: Some(101), signal: None }
+        );
+        // SIGSEGV
+        let run = process_output(11, false);
+        assert_eq!(
+            verdict_of(&run, Ok(String::new())),
+            Verdict::Re { code: None, signal: Some(11) }
+        );
+        assert_eq!(Verdict::Re { code: None, signal: Some(11) }.to_string(), "RE(SIGSEGV)");
+        let run = process_output(9, true);
+        assert_eq!(verdict_of(&run, Ok("Score = 10".to_string())), Verdict::Tle);
+    }
+
+    #[test]
+    fn test_extract_panic_message() {
+        let stderr = "# cmt\nthread 'main' panicked at src/main.rs:3:5:\nindex out of bounds\nnote: run with `RUST_BACKTRACE=1`\n";
+        assert_eq!(
+            CaseResult::extract_panic_message(stderr),
+            Some("index out of bounds (src/main.rs:3:5)".to_string())
+        );
+        let old = "thread 'main' panicked at 'attempt to add with overflow', src/main.rs:10:9\n";
+        assert_eq!(
+            CaseResult::extract_panic_message(old),
+            Some("attempt to add with overflow (src/main.rs:10:9)".to_string())
+        );
+        assert_eq!(CaseResult::extract_panic_message("# ok\n"), None);
     }
 
     #[test]
@@ -596,4 +1740,63 @@ mod tests {
         let toml = cfg.generate_toml_with_comments();
         assert!(toml.contains("score_regex = \"Score = (\\\\d+)\""));
     }
+
+    #[test]
+    fn test_generate_toml_roundtrip() {
+        let roundtrip = |cfg: &Config| {
+            let toml_str = cfg.generate_toml_with_comments();
+            let parsed: Config = toml::from_str(&toml_str).unwrap();
+            assert_eq!(serde_json::to_value(&parsed).unwrap(), serde_json::to_value(cfg).unwrap(), "{}", toml_str);
+            toml_str
+        };
+        let toml_str = roundtrip(&Config::default_config());
+        assert!(toml_str.contains("# time_limit = 2.0"));
+
+        // 全てのフィールドを既定値以外にしても元に戻る
+        let mut cfg = test_config();
+        cfg.build = BuildConfig { enable: true, command: "cargo build -r --bin a".to_string() };
+        cfg.test.no_evaluate = true;
+        cfg.test.use_tester = true;
+        cfg.test.vis = "./vis".to_string();
+        cfg.test.tester = "./tester".to_string();
+        cfg.test.score_type = ScoreType::Float;
+        cfg.test.time_limit = Some(3.5);
+        cfg.test.baseline = Some("previous".to_string());
+        cfg.test.objective = Objective::Min;
+        cfg.test.gen = Some("cargo run --bin gen -r --".to_string());
+        cfg.test.seeds = Some("1000-1999".to_string());
+        cfg.test.format = OutputFormat::Jsonl;
+        cfg.test.tui = true;
+        cfg.test.cache = true;
+        cfg.test.cache_env = vec!["MUL".to_string(), "A\"B".to_string()];
+        cfg.sets.insert("small".to_string(), "0-9 ^3".to_string());
+        cfg.sweep = toml::from_str(
+            r#"grid = { T0 = [1000, 2.0], MODE = ["a\"b"] }
+            list = [{ T0 = 5, FLAG = true }]"#,
+        )
+        .unwrap();
+        cfg.tune = toml::from_str(
+            r#"strategy = "halving"
+            trials = 27
+            seed = 7
+            output = "best.json"
+            features = ["N", "_", "M"]
+            buckets = { N = [50, 100.5] }
+            bins = 2
+            table = "src/table.rs"
+            [params]
+            T0 = { type = "float", min = 1, max = 1e4, log = true }
+            N = { type = "int", min = 1, max = 5 }
+            M = { type = "choice", values = ["a", 2] }"#,
+        )
+        .unwrap();
+        cfg.profiles = toml::from_str(
+            r#"fast = { bin = "./fast \"x\"", build = "make fast", env = { T0 = 1.5, MODE = "a" } }
+            plain = {}"#,
+        )
+        .unwrap();
+        cfg.sequential = SequentialConfig { enable: true, batch: 50, min_cases: 30 };
+        cfg.watch = WatchConfig { paths: vec!["src/**/*.rs".to_string(), "a \"b\".txt".to_string()], debounce: 1.0 };
+        roundtrip(&cfg);
+    }
 }
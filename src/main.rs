@@ -2,7 +2,12 @@ use clap::Parser;
 use std::fs;
 use std::path::Path;
 
-use cargo_heu::{Config, Heu};
+use cargo_heu::baseline::Baseline;
+use cargo_heu::compare::Side;
+use cargo_heu::best::BestScores;
+use cargo_heu::output::OutputFormat;
+use cargo_heu::tune::Strategy;
+use cargo_heu::{history, process, report, Config, Heu};
 
 #[derive(Parser)]
 #[command(name = "cargo-huu", about = "Test harness for heuristic programming contests")]
@@ -20,20 +25,205 @@ enum Sub {
 
 #[derive(Parser)]
 pub struct Args {
-    /// Test cases (e.g. 0 1 3-5)
-    cases: Vec<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    run: RunArgs,
 
     /// Config file path (default: ./heu.toml)
-    #[arg(short = 'f', long = "config")]
+    #[arg(short = 'f', long = "config", global = true)]
     config: Option<String>,
 
+    /// Run without evaluation (skip visualizer scoring)
+    #[arg(short = 'n', long = "no-evaluate")]
+    no_evaluate: bool,
+
+    /// Compare each case against a past run: run ID, "best" or "previous"
+    #[arg(short = 'b', long = "baseline")]
+    baseline: Option<String>,
+
+    /// Output format: human, json, jsonl, csv or tsv
+    #[arg(long = "format")]
+    format: Option<OutputFormat>,
+
+    /// Run cases in batches and stop once the difference from the baseline is significant
+    #[arg(long)]
+    sequential: bool,
+
+    /// Cases per batch in sequential mode (overrides sequential.batch; implies --sequential)
+    #[arg(long = "batch")]
+    batch: Option<usize>,
+
+    /// Show a live dashboard with progress, ETA, running cases and worst cases (terminal only)
+    #[arg(long)]
+    tui: bool,
+
+    /// Reuse stored results for cases whose binary, input and env are unchanged
+    #[arg(long)]
+    cache: bool,
+
+    /// Rebuild and rerun whenever the files in watch.paths change
+    #[arg(short = 'w', long)]
+    watch: bool,
+
+    /// Skip cases completed by the latest run with the same binary and config, and merge the results
+    #[arg(long, conflicts_with_all = ["rerun_failed", "watch"])]
+    resume: bool,
+
+    /// Rerun only the failed cases (within the given cases, if any) of the latest run with the same binary and config,
+    /// and merge the results into one record
+    #[arg(long, conflicts_with = "watch")]
+    rerun_failed: bool,
+}
+
+/// ケースを実行するコマンドに共通のオプション。
+#[derive(clap::Args)]
+struct RunArgs {
+    /// Test cases (e.g. 0 1 3-5). Defaults to test.cases
+    cases: Vec<String>,
+
     /// Number of parallel threads
     #[arg(short = 'j', long = "threads")]
     threads: Option<usize>,
 
-    /// Run without evaluation (skip visualizer scoring)
-    #[arg(short = 'n', long = "no-evaluate")]
-    no_evaluate: bool,
+    /// Time limit per case in seconds (overrides test.time_limit)
+    #[arg(long = "tl")]
+    time_limit: Option<f64>,
+}
+
+impl RunArgs {
+    /// CLI引数でconfigのフィールドを上書きする。
+    fn apply(&self, config: &mut Config) {
+        if !self.cases.is_empty() {
+            config.test.cases = self.cases.join(" ");
+        }
+        if let Some(threads) = self.threads {
+            config.test.threads = threads;
+        }
+        if let Some(tl) = self.time_limit {
+            config.test.time_limit = Some(tl);
+        }
+    }
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    /// List past runs saved under out_dir/history
+    History {
+        /// Show only the N most recent runs
+        #[arg(short = 'n', long = "limit")]
+        limit: Option<usize>,
+    },
+    /// Generate input files with test.gen (skips existing inputs)
+    Gen {
+        #[command(flatten)]
+        run: RunArgs,
+
+        /// Regenerate inputs that already exist
+        #[arg(long)]
+        force: bool,
+    },
+    /// Run the cases for each parameter combination in [sweep] and compare totals
+    Sweep {
+        #[command(flatten)]
+        run: RunArgs,
+
+        /// Parameter values passed as env vars (e.g. -p T0=1000,2000). Replaces the values of the same name in [sweep.grid]
+        #[arg(short = 'p', long = "param")]
+        params: Vec<String>,
+    },
+    /// Search the parameter space in [tune] and write the best parameters to a file
+    Tune {
+        #[command(flatten)]
+        run: RunArgs,
+
+        /// Number of trials (overrides tune.trials)
+        #[arg(short = 'n', long = "trials")]
+        trials: Option<usize>,
+
+        /// Search strategy: random, local or halving (overrides tune.strategy)
+        #[arg(long = "strategy")]
+        strategy: Option<Strategy>,
+
+        /// Random seed (overrides tune.seed)
+        #[arg(long = "seed")]
+        seed: Option<u64>,
+
+        /// Output file for the best parameters (overrides tune.output)
+        #[arg(short = 'o', long = "output")]
+        output: Option<String>,
+
+        /// Discard the trial log and start over
+        #[arg(long)]
+        fresh: bool,
+
+        /// Only rebuild the per-bucket table (tune.features) from the trial log without running trials
+        #[arg(long)]
+        table: bool,
+    },
+    /// Run two solutions on the same cases (interleaved) and compare them with a paired test
+    Compare {
+        #[command(flatten)]
+        run: RunArgs,
+
+        /// Command for side A (default: test.bin)
+        #[arg(long = "bin-a")]
+        bin_a: Option<String>,
+
+        /// Command for side B
+        #[arg(long = "bin-b")]
+        bin_b: Option<String>,
+
+        /// Profile in [profiles] for side A
+        #[arg(long = "profile-a")]
+        profile_a: Option<String>,
+
+        /// Profile in [profiles] for side B
+        #[arg(long = "profile-b")]
+        profile_b: Option<String>,
+    },
+    /// Render a run as a self-contained HTML report
+    Report {
+        /// Run ID (default: the latest run)
+        id: Option<String>,
+
+        /// Output file (default: out_dir/report/<ID>.html)
+        #[arg(short = 'o', long = "output")]
+        output: Option<String>,
+
+        /// Compare against a past run: run ID, "best" or "previous"
+        #[arg(short = 'b', long = "baseline")]
+        baseline: Option<String>,
+    },
+}
+
+/// 実行記録から HTML レポートを作り、出力先を返す。
+fn write_report(
+    config: &Config,
+    id: Option<&str>,
+    output: Option<&str>,
+    baseline: Option<&str>,
+) -> std::io::Result<std::path::PathBuf> {
+    let out_dir = &config.test.out_dir;
+    let mut records = history::load_all(out_dir)?;
+    let latest = records.last().map(|r| r.id.clone());
+    let record = match id {
+        Some(id) => history::load(out_dir, id)?,
+        None => records
+            .pop()
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "no runs in history"))?,
+    };
+    let is_latest = latest.as_deref() == Some(record.id.as_str());
+    let objective = record.config.test.objective;
+    let baseline = match baseline.or(config.test.baseline.as_deref()) {
+        Some(spec) => Some(Baseline::resolve_for(out_dir, spec, &record, objective)?),
+        None => None,
+    };
+    let best = BestScores::load(out_dir)?;
+    let path = output.map_or_else(|| report::report_path(out_dir, &record.id), Into::into);
+    report::write(&path, &record, baseline.as_ref(), &best, is_latest)?;
+    Ok(path)
 }
 
 fn load_config(config_path: Option<&str>) -> Config {
@@ -61,25 +251,139 @@ fn main() {
 
     let args = match cli.sub {
         Some(Sub::Heu(a)) => a,
-        None => Args::parse_from(std::env::args().skip(0)),
+        None => Args::parse_from(std::env::args()),
     };
 
     let mut config = load_config(args.config.as_deref());
+    process::install_interrupt_handler();
 
-    // CLI引数でconfigのフィールドを上書き
-    if !args.cases.is_empty() {
-        config.test.cases = args.cases.join(" ");
-    }
-    if let Some(threads) = args.threads {
-        config.test.threads = threads;
+    match args.command {
+        Some(Command::History { limit }) => {
+            if let Err(e) = history::print_list(&config.test.out_dir, limit) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Command::Gen { run, force }) => {
+            run.apply(&mut config);
+            match Heu::try_new(config).and_then(|heu| heu.generate_inputs(force)) {
+                Ok(n) => eprintln!("Generated {} inputs", n),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        Some(Command::Sweep { run, params }) => {
+            run.apply(&mut config);
+            let result = params
+                .iter()
+                .try_for_each(|p| config.sweep.add_param_arg(p))
+                .and_then(|_| Heu::try_new(config))
+                .and_then(|heu| heu.sweep());
+            if let Err(e) = result {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Command::Tune { run, trials, strategy, seed, output, fresh, table }) => {
+            run.apply(&mut config);
+            if let Some(trials) = trials {
+                config.tune.trials = trials;
+            }
+            if let Some(strategy) = strategy {
+                config.tune.strategy = strategy;
+            }
+            if let Some(seed) = seed {
+                config.tune.seed = seed;
+            }
+            if output.is_some() {
+                config.tune.output = output;
+            }
+            let result = Heu::try_new(config).and_then(|heu| {
+                if table {
+                    heu.tune_table().map(|_| ())
+                } else {
+                    heu.tune(fresh).map(|_| ())
+                }
+            });
+            if let Err(e) = result {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Command::Compare { run, bin_a, bin_b, profile_a, profile_b }) => {
+            run.apply(&mut config);
+            let result = if bin_b.is_none() && profile_b.is_none() {
+                Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "specify --bin-b or --profile-b"))
+            } else {
+                Side::resolve(&config, bin_a.as_deref(), profile_a.as_deref())
+                    .and_then(|a| Ok((a, Side::resolve(&config, bin_b.as_deref(), profile_b.as_deref())?)))
+                    .and_then(|(a, b)| Heu::try_new(config)?.compare(&a, &b))
+            };
+            if let Err(e) = result {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Command::Report { id, output, baseline }) => {
+            match write_report(&config, id.as_deref(), output.as_deref(), baseline.as_deref()) {
+                Ok(path) => eprintln!("Wrote report: {}", path.display()),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        None => {}
     }
+
+    // CLI引数でconfigのフィールドを上書き
+    args.run.apply(&mut config);
     if args.no_evaluate {
         config.test.no_evaluate = true;
     }
+    if let Some(baseline) = args.baseline {
+        config.test.baseline = Some(baseline);
+    }
+    if let Some(format) = args.format {
+        config.test.format = format;
+    }
+    if args.tui {
+        config.test.tui = true;
+    }
+    if args.cache {
+        config.test.cache = true;
+    }
+    if args.sequential {
+        config.sequential.enable = true;
+    }
+    if let Some(batch) = args.batch {
+        config.sequential.enable = true;
+        config.sequential.batch = batch;
+    }
 
-    let heu = Heu::new(config);
-
-    if let Err(e) = heu.execute() {
+    let result = Heu::try_new(config).and_then(|heu| {
+        if args.watch {
+            heu.watch()
+        } else if args.resume {
+            heu.resume().map(|_| ())
+        } else if args.rerun_failed {
+            heu.rerun_failed(!args.run.cases.is_empty()).map(|_| ())
+        } else {
+            heu.execute().map(|_| ())
+        }
+    });
+    if let Err(e) = result {
+        if e.kind() == std::io::ErrorKind::Interrupted {
+            std::process::exit(130);
+        }
         eprintln!("Error: {}", e);
         std::process::exit(1);
     }
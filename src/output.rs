@@ -0,0 +1,291 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{CaseResult, Summary};
+
+/// 結果の出力形式。
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// 人が読むための1ケース1行の表示。
+    #[default]
+    Human,
+    /// 全ケースと集計を1つの JSON オブジェクトとして最後に出力する。
+    Json,
+    /// 1ケース1行の JSON。最後に集計の行を出力する。
+    Jsonl,
+    /// ケースの表と集計の表を空行で区切って出力する。
+    Csv,
+    Tsv,
+}
+
+impl OutputFormat {
+    pub fn is_human(self) -> bool {
+        self == OutputFormat::Human
+    }
+
+    fn delimiter(self) -> Option<char> {
+        match self {
+            OutputFormat::Csv => Some(','),
+            OutputFormat::Tsv => Some('\t'),
+            _ => None,
+        }
+    }
+
+    /// ケースのレコードより前に出力するもの (CSV / TSV のヘッダ)。
+    pub fn print_header(self) {
+        if let Some(d) = self.delimiter() {
+            println!("{}", CASE_COLUMNS.join(&d.to_string()));
+        }
+    }
+
+    /// 1ケースのレコードを出力する。JSON は最後にまとめて出力するのでここでは何もしない。
+    pub fn print_case(self, result: &CaseResult) {
+        match self {
+            OutputFormat::Jsonl => println!("{}", to_json(&Record::Case(result))),
+            OutputFormat::Csv | OutputFormat::Tsv => println!("{}", self.row(&case_row(result))),
+            OutputFormat::Human | OutputFormat::Json => {}
+        }
+    }
+
+    /// 集計のレコードを出力する。JSON の場合は全ケースの結果も合わせて出力する。
+    pub fn print_summary(self, results: &[CaseResult], summary: &Summary) {
+        match self {
+            OutputFormat::Json => {
+                let doc = Document { cases: results, summary: SummaryRecord::new(summary) };
+                println!("{}", serde_json::to_string_pretty(&doc).unwrap_or_default());
+            }
+            OutputFormat::Jsonl => println!("{}", to_json(&Record::Summary(SummaryRecord::new(summary)))),
+            OutputFormat::Csv | OutputFormat::Tsv => {
+                println!();
+                println!("{}", self.row(&SUMMARY_COLUMNS.map(String::from)));
+                println!("{}", self.row(&summary_row(summary)));
+            }
+            OutputFormat::Human => {}
+        }
+    }
+
+    fn row(self, fields: &[String]) -> String {
+        let d = self.delimiter().unwrap_or(',');
+        fields.iter().map(|f| escape_field(f, d)).collect::<Vec<_>>().join(&d.to_string())
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(OutputFormat::Human),
+            "json" => Ok(OutputFormat::Json),
+            "jsonl" => Ok(OutputFormat::Jsonl),
+            "csv" => Ok(OutputFormat::Csv),
+            "tsv" => Ok(OutputFormat::Tsv),
+            _ => Err(format!("unknown format '{}' (expected human, json, jsonl, csv or tsv)", s)),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            OutputFormat::Human => "human",
+            OutputFormat::Json => "json",
+            OutputFormat::Jsonl => "jsonl",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Tsv => "tsv",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// JSONL の1行。"type" でケースと集計を区別する。
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Record<'a> {
+    Case(&'a CaseResult),
+    Summary(SummaryRecord<'a>),
+}
+
+/// 集計のレコード。他の列と同じく `failed` は件数にし、失敗したケース番号は `failed_cases` に入れる。
+#[derive(Serialize)]
+struct SummaryRecord<'a> {
+    failed: usize,
+    #[serde(flatten)]
+    summary: &'a Summary,
+}
+
+impl<'a> SummaryRecord<'a> {
+    fn new(summary: &'a Summary) -> Self {
+        Self { failed: summary.failed.len(), summary }
+    }
+}
+
+/// `--format json` の出力全体。
+#[derive(Serialize)]
+struct Document<'a> {
+    cases: &'a [CaseResult],
+    summary: SummaryRecord<'a>,
+}
+
+fn to_json<T: Serialize>(value: &T) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+const CASE_COLUMNS: [&str; 14] = [
+    "case",
+    "score",
+    "verdict",
+    "elapsed",
+    "cpu_time",
+    "max_rss_kb",
+    "exit_code",
+    "signal",
+    "panic_message",
+    "comments",
+    "inf",
+    "outf",
+    "errf",
+    "cached",
+];
+
+const SUMMARY_COLUMNS: [&str; 21] = [
+    "cases",
+    "failed",
+    "failed_cases",
+    "total",
+    "relative_total",
+    "mean",
+    "median",
+    "stddev",
+    "min_case",
+    "min_score",
+    "max_case",
+    "max_score",
+    "geometric_mean",
+    "log_score_sum",
+    "max_elapsed",
+    "p95_elapsed",
+    "mean_elapsed",
+    "rel_diff",
+    "rel_diff_ci_low",
+    "rel_diff_ci_high",
+    "p_value",
+];
+
+fn opt<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+fn case_row(r: &CaseResult) -> Vec<String> {
+    vec![
+        r.case.to_string(),
+        opt(r.score),
+        r.verdict.to_string(),
+        format!("{:.3}", r.elapsed),
+        opt(r.usage.map(|u| format!("{:.3}", u.cpu_time()))),
+        opt(r.usage.map(|u| u.max_rss_kb)),
+        opt(r.exit_code),
+        opt(r.signal),
+        r.panic_message.clone().unwrap_or_default(),
+        r.comments.clone(),
+        r.inf.clone(),
+        r.outf.clone(),
+        r.errf.clone(),
+        r.cached.to_string(),
+    ]
+}
+
+fn summary_row(s: &Summary) -> Vec<String> {
+    vec![
+        s.cases.to_string(),
+        s.failed.len().to_string(),
+        s.failed.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(" "),
+        s.total.to_string(),
+        s.relative_total.to_string(),
+        opt(s.mean),
+        opt(s.median),
+        opt(s.stddev),
+        opt(s.min.map(|m| m.case)),
+        opt(s.min.map(|m| m.score)),
+        opt(s.max.map(|m| m.case)),
+        opt(s.max.map(|m| m.score)),
+        opt(s.geometric_mean),
+        opt(s.log_score_sum),
+        format!("{:.3}", s.max_elapsed),
+        format!("{:.3}", s.p95_elapsed),
+        format!("{:.3}", s.mean_elapsed),
+        opt(s.significance.map(|x| x.mean_diff)),
+        opt(s.significance.map(|x| x.ci_low)),
+        opt(s.significance.map(|x| x.ci_high)),
+        opt(s.significance.map(|x| x.p_value)),
+    ]
+}
+
+/// CSV では区切り文字・引用符・改行を含むフィールドを引用符で囲む。
+/// TSV では引用符を使わず、タブと改行を空白に置き換える。
+fn escape_field(field: &str, delimiter: char) -> String {
+    if delimiter == '\t' {
+        return field.replace(['\t', '\n', '\r'], " ");
+    }
+    if field.contains([delimiter, '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_field() {
+        assert_eq!(escape_field("plain", ','), "plain");
+        assert_eq!(escape_field("a,b", ','), "\"a,b\"");
+        assert_eq!(escape_field("say \"hi\"", ','), "\"say \"\"hi\"\"\"");
+        assert_eq!(escape_field("a\tb\nc", '\t'), "a b c");
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("jsonl".parse::<OutputFormat>(), Ok(OutputFormat::Jsonl));
+        assert!("xml".parse::<OutputFormat>().is_err());
+    }
+
+    #[test]
+    fn test_case_records() {
+        let mut r = CaseResult::ok_for_test(3, 1234);
+        r.inf = "in/0003.txt".to_string();
+        r.outf = "out/0003.txt".to_string();
+        r.comments = "a,b".to_string();
+
+        let row = OutputFormat::Csv.row(&case_row(&r));
+        assert!(row.starts_with("3,1234,OK,"), "{}", row);
+        assert!(row.contains(",\"a,b\",in/0003.txt,out/0003.txt,"), "{}", row);
+        assert_eq!(case_row(&r).len(), CASE_COLUMNS.len());
+
+        let json: serde_json::Value = serde_json::from_str(&to_json(&Record::Case(&r))).unwrap();
+        assert_eq!(json["type"], "case");
+        assert_eq!(json["case"], 3);
+        assert_eq!(json["score"], 1234);
+        assert_eq!(json["verdict"], "OK");
+        assert_eq!(json["inf"], "in/0003.txt");
+    }
+
+    #[test]
+    fn test_summary_records() {
+        let results = [CaseResult::ok_for_test(0, 10), CaseResult::failed_for_test(1), CaseResult::failed_for_test(2)];
+        let summary = Summary::new(&results, &crate::best::BestScores::default(), crate::Objective::Max, crate::ScoreType::Int);
+
+        let row = summary_row(&summary);
+        assert_eq!(row.len(), SUMMARY_COLUMNS.len());
+        assert_eq!(&row[..3], ["3", "2", "1 2"]);
+
+        let json: serde_json::Value = serde_json::from_str(&to_json(&Record::Summary(SummaryRecord::new(&summary)))).unwrap();
+        assert_eq!(json["type"], "summary");
+        assert_eq!(json["failed"], 2);
+        assert_eq!(json["failed_cases"], serde_json::json!([1, 2]));
+    }
+}
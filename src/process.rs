@@ -0,0 +1,315 @@
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read, Write};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicI32, AtomicU8, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 子プロセスの終了状態をポーリングする間隔。
+/// 短すぎると並列実行中のケースから CPU 時間を奪うので、数ミリ秒にしておく。
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// 実行中のケースを中断したかどうか。
+static CANCEL_STATE: AtomicU8 = AtomicU8::new(RUNNING);
+const RUNNING: u8 = 0;
+/// プログラムから中断した (ウォッチモードでの再実行など)。
+const CANCELLED: u8 = 1;
+/// Ctrl-C で中断した。
+const INTERRUPTED: u8 = 2;
+
+/// 実行中の子プロセスのプロセスグループ ID。0 は空き。
+/// シグナルハンドラから読めるよう、ロックを使わずアトミック変数の配列で持つ。
+static LIVE_GROUPS: [AtomicI32; MAX_LIVE_GROUPS] = [const { AtomicI32::new(0) }; MAX_LIVE_GROUPS];
+const MAX_LIVE_GROUPS: usize = 256;
+
+/// 実行中のプロセスグループとして登録し、drop で登録を外す。
+struct LiveGroup(Option<usize>);
+
+impl LiveGroup {
+    fn register(pgid: i32) -> Self {
+        let slot = LIVE_GROUPS
+            .iter()
+            .position(|g| g.compare_exchange(0, pgid, Ordering::SeqCst, Ordering::SeqCst).is_ok());
+        Self(slot)
+    }
+}
+
+impl Drop for LiveGroup {
+    fn drop(&mut self) {
+        if let Some(i) = self.0 {
+            LIVE_GROUPS[i].store(0, Ordering::SeqCst);
+        }
+    }
+}
+
+/// 実行中の子プロセスを kill し、新しいケースを始めないようにする。
+pub fn cancel() {
+    let _ = CANCEL_STATE.compare_exchange(RUNNING, CANCELLED, Ordering::SeqCst, Ordering::SeqCst);
+}
+
+pub fn is_cancelled() -> bool {
+    CANCEL_STATE.load(Ordering::SeqCst) != RUNNING
+}
+
+/// Ctrl-C で中断されたか。
+pub fn is_interrupted() -> bool {
+    CANCEL_STATE.load(Ordering::SeqCst) == INTERRUPTED
+}
+
+/// [`cancel`] による中断を取り消し、再び実行できるようにする。Ctrl-C による中断は取り消さない。
+pub fn reset_cancel() {
+    let _ = CANCEL_STATE.compare_exchange(CANCELLED, RUNNING, Ordering::SeqCst, Ordering::SeqCst);
+}
+
+/// Ctrl-C (SIGINT) で実行を中断するようにする。子プロセスは別のプロセスグループで動くので、
+/// 端末からの SIGINT は届かず、[`run`] が kill する。2回目の Ctrl-C では実行中のプロセスグループを
+/// kill してすぐに終了する。
+#[cfg(unix)]
+pub fn install_interrupt_handler() {
+    extern "C" fn on_sigint(_: libc::c_int) {
+        if CANCEL_STATE.swap(INTERRUPTED, Ordering::SeqCst) == INTERRUPTED {
+            for g in &LIVE_GROUPS {
+                let pgid = g.load(Ordering::SeqCst);
+                if pgid > 0 {
+                    // SAFETY: kill は async-signal-safe
+                    unsafe { libc::kill(-pgid, libc::SIGKILL) };
+                }
+            }
+            // SAFETY: _exit は async-signal-safe
+            unsafe { libc::_exit(130) };
+        }
+    }
+    // SAFETY: ハンドラはアトミック変数の操作と kill, _exit しか行わない
+    unsafe {
+        libc::signal(libc::SIGINT, on_sigint as extern "C" fn(libc::c_int) as libc::sighandler_t);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn install_interrupt_handler() {}
+
+fn cancelled_error() -> io::Error {
+    io::Error::new(io::ErrorKind::Interrupted, if is_interrupted() { "interrupted" } else { "cancelled" })
+}
+
+/// 中断されていればエラーを返す。
+pub fn check_cancelled() -> io::Result<()> {
+    if is_cancelled() {
+        Err(cancelled_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// 子プロセスのリソース使用量。
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    /// ピーク RSS (KiB)。
+    pub max_rss_kb: u64,
+    /// ユーザー CPU 時間 (秒)。
+    pub user_time: f64,
+    /// システム CPU 時間 (秒)。
+    pub sys_time: f64,
+}
+
+impl ResourceUsage {
+    pub fn cpu_time(&self) -> f64 {
+        self.user_time + self.sys_time
+    }
+
+    pub fn max_rss_mib(&self) -> f64 {
+        self.max_rss_kb as f64 / 1024.0
+    }
+}
+
+/// 子プロセス1回分の実行結果。
+pub struct ProcessOutput {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    pub status: Option<ExitStatus>,
+    pub elapsed: f64,
+    /// 時間制限を超えたため kill された場合に true。
+    pub timed_out: bool,
+    /// wait4 で取得したリソース使用量。unix 以外では None。
+    /// tester 経由の場合は tester と、tester が回収した子プロセスの合算になる。
+    pub usage: Option<ResourceUsage>,
+}
+
+/// コマンドを起動し、stdin に `input` を書き込んで終了を待つ。
+/// `time_limit` を超えた場合はプロセスグループごと kill し、それまでの出力を返す。
+/// `input` が None の場合、stdin は呼び出し側で設定したものを使う。
+/// [`cancel`] された場合もプロセスグループごと kill し、`Interrupted` のエラーを返す。
+pub fn run(cmd: &mut Command, input: Option<&[u8]>, time_limit: Option<Duration>) -> io::Result<ProcessOutput> {
+    if input.is_some() {
+        cmd.stdin(Stdio::piped());
+    }
+    cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        // tester 経由の孫プロセスもまとめて kill できるよう、新しいプロセスグループで起動する
+        cmd.process_group(0);
+    }
+
+    let start = Instant::now();
+    let mut child = cmd.spawn()?;
+    // process_group(0) で起動しているので pgid == pid
+    let _live = LiveGroup::register(child.id() as i32);
+
+    let stdin_writer = match (child.stdin.take(), input) {
+        (Some(mut stdin), Some(data)) => {
+            let data = data.to_vec();
+            // 途中で終了したプロセスへの書き込みは BrokenPipe になるので無視する
+            Some(thread::spawn(move || {
+                let _ = stdin.write_all(&data);
+            }))
+        }
+        _ => None,
+    };
+    let stdout_reader = spawn_reader(child.stdout.take());
+    let stderr_reader = spawn_reader(child.stderr.take());
+
+    let deadline = time_limit.map(|tl| start + tl);
+    let mut timed_out = false;
+    let (status, usage) = loop {
+        if let Some((status, usage)) = wait_child(&mut child, true)? {
+            break (Some(status), usage);
+        }
+        if is_cancelled() {
+            kill_tree(&mut child);
+            let _ = wait_child(&mut child, false);
+            return Err(cancelled_error());
+        }
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            timed_out = true;
+            kill_tree(&mut child);
+            match wait_child(&mut child, false) {
+                Ok(Some((status, usage))) => break (Some(status), usage),
+                _ => break (None, None),
+            }
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+    let elapsed = start.elapsed().as_secs_f64();
+
+    if let Some(h) = stdin_writer {
+        let _ = h.join();
+    }
+    let stdout = stdout_reader.join().unwrap_or_default();
+    let stderr = stderr_reader.join().unwrap_or_default();
+
+    Ok(ProcessOutput { stdout, stderr, status, elapsed, timed_out, usage })
+}
+
+/// 子プロセスの終了を待ち、終了状態とリソース使用量を返す。
+/// `nohang` が true の場合、まだ終了していなければ None を返す。
+#[cfg(unix)]
+fn wait_child(child: &mut Child, nohang: bool) -> io::Result<Option<(ExitStatus, Option<ResourceUsage>)>> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let pid = child.id() as libc::pid_t;
+    let flags = if nohang { libc::WNOHANG } else { 0 };
+    let mut status: libc::c_int = 0;
+    // SAFETY: rusage は全フィールドが整数の C 構造体なのでゼロ初期化してよい
+    let mut ru: libc::rusage = unsafe { std::mem::zeroed() };
+    loop {
+        let ret = unsafe { libc::wait4(pid, &mut status, flags, &mut ru) };
+        if ret == 0 {
+            return Ok(None);
+        }
+        if ret < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err);
+        }
+        return Ok(Some((ExitStatus::from_raw(status), Some(usage_from_rusage(&ru)))));
+    }
+}
+
+#[cfg(not(unix))]
+fn wait_child(child: &mut Child, nohang: bool) -> io::Result<Option<(ExitStatus, Option<ResourceUsage>)>> {
+    if nohang {
+        Ok(child.try_wait()?.map(|s| (s, None)))
+    } else {
+        Ok(Some((child.wait()?, None)))
+    }
+}
+
+#[cfg(unix)]
+fn usage_from_rusage(ru: &libc::rusage) -> ResourceUsage {
+    let secs = |tv: libc::timeval| tv.tv_sec as f64 + tv.tv_usec as f64 * 1e-6;
+    // ru_maxrss は Linux では KiB、macOS ではバイト単位
+    let max_rss_kb = if cfg!(target_os = "macos") {
+        ru.ru_maxrss as u64 / 1024
+    } else {
+        ru.ru_maxrss as u64
+    };
+    ResourceUsage { max_rss_kb, user_time: secs(ru.ru_utime), sys_time: secs(ru.ru_stime) }
+}
+
+fn spawn_reader<R: Read + Send + 'static>(pipe: Option<R>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        buf
+    })
+}
+
+/// 子プロセスとその子孫を kill する。
+fn kill_tree(child: &mut Child) {
+    #[cfg(unix)]
+    unsafe {
+        // process_group(0) で起動しているので pgid == pid
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+    let _ = child.kill();
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_captures_output() {
+        let mut cmd = Command::new("cat");
+        let out = run(&mut cmd, Some(b"hello"), None).unwrap();
+        assert_eq!(out.stdout, b"hello");
+        assert!(!out.timed_out);
+        assert!(out.status.unwrap().success());
+    }
+
+    #[test]
+    fn test_run_measures_usage() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "i=0; while [ $i -lt 20000 ]; do i=$((i+1)); done; exit 3"]);
+        let out = run(&mut cmd, Some(b""), None).unwrap();
+        assert_eq!(out.status.unwrap().code(), Some(3));
+        let usage = out.usage.unwrap();
+        assert!(usage.max_rss_kb > 0);
+        assert!(usage.cpu_time() > 0.0);
+    }
+
+    #[test]
+    fn test_live_group() {
+        let pgid = 999_999_999;
+        let registered = |pgid| LIVE_GROUPS.iter().any(|g| g.load(Ordering::SeqCst) == pgid);
+        let live = LiveGroup::register(pgid);
+        assert!(registered(pgid));
+        drop(live);
+        assert!(!registered(pgid));
+    }
+
+    #[test]
+    fn test_run_kills_on_time_limit() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo partial; sleep 5"]);
+        let out = run(&mut cmd, Some(b""), Some(Duration::from_millis(200))).unwrap();
+        assert!(out.timed_out);
+        assert!(out.elapsed < 5.0);
+        assert_eq!(out.stdout, b"partial\n");
+    }
+}
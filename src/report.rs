@@ -0,0 +1,506 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::baseline::{Baseline, WinLoss};
+use crate::best::BestScores;
+use crate::history::RunRecord;
+use crate::{format_with_commas, Score, Summary};
+
+/// ヒストグラムの棒の数の上限。
+const HISTOGRAM_BINS: usize = 20;
+
+/// 既定の出力先 `{out_dir}/report/{id}.html`。
+pub fn report_path(out_dir: &str, id: &str) -> PathBuf {
+    Path::new(out_dir).join("report").join(format!("{}.html", id))
+}
+
+/// ビジュアライザの成果物を置くディレクトリ `{out_dir}/vis`。
+/// "0003.svg" のようにケース番号で始まるファイルがレポートからリンクされる。
+pub fn vis_dir(out_dir: &str) -> PathBuf {
+    Path::new(out_dir).join("vis")
+}
+
+/// 実行記録を1つの HTML ファイルとして書き出す。
+/// 出力・stderr・ビジュアライザの成果物は次の実行で上書きされるので、`latest` (最新の実行) の場合だけリンクする。
+pub fn write(
+    path: &Path,
+    record: &RunRecord,
+    baseline: Option<&Baseline>,
+    best: &BestScores,
+    latest: bool,
+) -> io::Result<()> {
+    let out_dir = &record.config.test.out_dir;
+    let summary = Summary::new(&record.cases, best, record.config.test.objective, record.config.test.score_type);
+    let report = Report {
+        record,
+        summary: &summary,
+        baseline,
+        best,
+        base_dir: path.parent().unwrap_or(Path::new(".")).to_path_buf(),
+        artifacts: if latest { vis_artifacts(&vis_dir(out_dir))? } else { BTreeMap::new() },
+        latest,
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, report.render())
+}
+
+/// `{out_dir}/vis` 以下のファイルをケース番号ごとにまとめる。
+fn vis_artifacts(dir: &Path) -> io::Result<BTreeMap<u32, Vec<PathBuf>>> {
+    let mut ret: BTreeMap<u32, Vec<PathBuf>> = BTreeMap::new();
+    if !dir.exists() {
+        return Ok(ret);
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if let Some(case) = path.file_name().and_then(|n| n.to_str()).and_then(artifact_case) {
+            ret.entry(case).or_default().push(path);
+        }
+    }
+    for paths in ret.values_mut() {
+        paths.sort();
+    }
+    Ok(ret)
+}
+
+/// ファイル名の先頭の数字の並びをケース番号とする ("0003.svg" → 3, "10000_a.png" → 10000)。
+fn artifact_case(name: &str) -> Option<u32> {
+    let end = name.find(|c: char| !c.is_ascii_digit()).unwrap_or(name.len());
+    name[..end].parse().ok()
+}
+
+struct Report<'a> {
+    record: &'a RunRecord,
+    summary: &'a Summary,
+    baseline: Option<&'a Baseline>,
+    best: &'a BestScores,
+    /// レポートを置くディレクトリ。リンクはここからの相対パスにする。
+    base_dir: PathBuf,
+    artifacts: BTreeMap<u32, Vec<PathBuf>>,
+    /// 最新の実行か。古い実行では out_dir のファイルが別の実行のものなのでリンクしない。
+    latest: bool,
+}
+
+impl Report<'_> {
+    fn render(&self) -> String {
+        let r = self.record;
+        let mut html = String::new();
+        let _ = write!(
+            html,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>heu report {id}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n<h1>Run {id}</h1>\n<p>{ts} / commit {commit} / bin <code>{bin}</code> / objective {obj}</p>\n",
+            id = escape(&r.id),
+            ts = escape(&r.timestamp),
+            commit = escape(r.git_commit.as_deref().unwrap_or("-")),
+            bin = escape(&r.config.test.bin),
+            obj = r.config.test.objective,
+        );
+        if !self.latest {
+            let _ = writeln!(
+                html,
+                "<p class=\"note\">Not the latest run: output, stderr and visualizer files in <code>{}</code> have been overwritten by later runs and are not linked.</p>",
+                escape(&r.config.test.out_dir)
+            );
+        }
+        html.push_str(&self.summary_section());
+        html.push_str("<div class=\"charts\">\n");
+        let scores: Vec<f64> = r.cases.iter().filter_map(|c| c.ok_score()).map(Score::as_f64).collect();
+        let elapsed: Vec<f64> = r.cases.iter().map(|c| c.elapsed).collect();
+        html.push_str(&histogram_svg("Score", &scores));
+        html.push_str(&histogram_svg("Elapsed (s)", &elapsed));
+        if let Some(b) = self.baseline {
+            html.push_str(&self.scatter_svg(b));
+        }
+        html.push_str("</div>\n");
+        html.push_str(&self.case_table());
+        html.push_str(&format!("<script>{}</script>\n</body>\n</html>\n", SCRIPT));
+        html
+    }
+
+    fn summary_section(&self) -> String {
+        let s = self.summary;
+        let stat = |v: Option<f64>| v.map_or("-".to_string(), |v| format_with_commas(format!("{:.2}", v)));
+        let case_score = |cs: Option<crate::stats::CaseScore>| {
+            cs.map_or("-".to_string(), |cs| format!("{} ({:04})", format_with_commas(cs.score), cs.case))
+        };
+        let mut items = vec![
+            ("TOTAL", format_with_commas(s.total)),
+            ("AVG", stat(s.mean)),
+            ("REL", format_with_commas(s.relative_total)),
+            ("MEDIAN", stat(s.median)),
+            ("STDDEV", stat(s.stddev)),
+            ("MIN", case_score(s.min)),
+            ("MAX", case_score(s.max)),
+            ("GMEAN", stat(s.geometric_mean)),
+            ("FAILED", format!("{}/{}", s.failed.len(), s.cases)),
+            ("ELAPSED", format!("max {:.2}s / p95 {:.2}s", s.max_elapsed, s.p95_elapsed)),
+        ];
+        if let Some(b) = self.baseline {
+            let mut wl = WinLoss::default();
+            for d in self.record.cases.iter().filter_map(|c| b.diff(c)) {
+                wl.add(&d);
+            }
+            items.push(("BASELINE", b.id.clone()));
+            items.push(("VS", b.total_diff(&self.record.cases).format()));
+            items.push(("WIN / LOSE / DRAW", format!("{} / {} / {}", wl.win, wl.lose, wl.draw)));
+        }
+        let mut html = String::from("<dl class=\"summary\">\n");
+        for (k, v) in items {
+            let _ = writeln!(html, "<div><dt>{}</dt><dd>{}</dd></div>", k, escape(&v));
+        }
+        html.push_str("</dl>\n");
+        html
+    }
+
+    fn case_table(&self) -> String {
+        let objective = self.record.config.test.objective;
+        let mut html = String::from("<table>\n<thead><tr>");
+        let mut headers = vec!["Case", "Score"];
+        if self.baseline.is_some() {
+            headers.extend(["Baseline", "Diff", "Ratio"]);
+        }
+        headers.extend(["Rel", "Verdict", "Elapsed", "CPU", "Mem (MiB)", "Comments", "Files"]);
+        for h in headers {
+            let _ = write!(html, "<th>{}</th>", h);
+        }
+        html.push_str("</tr></thead>\n<tbody>\n");
+
+        for c in &self.record.cases {
+            let class = if c.verdict.is_ok() { "" } else { " class=\"failed\"" };
+            let _ = write!(html, "<tr{}>", class);
+            let _ = write!(html, "<td>{:04}</td>", c.case);
+            html.push_str(&score_cell(c.ok_score()));
+            if let Some(b) = self.baseline {
+                let diff = b.diff(c);
+                html.push_str(&score_cell(diff.and_then(|d| d.base)));
+                let class = match diff.map(|d| d.ordering) {
+                    Some(std::cmp::Ordering::Greater) => "better",
+                    Some(std::cmp::Ordering::Less) => "worse",
+                    _ => "",
+                };
+                match diff.and_then(|d| d.delta()) {
+                    Some(d) => {
+                        let _ = write!(html, "<td class=\"{}\" data-v=\"{}\">{}</td>", class, d.as_f64(), format_with_commas(d));
+                    }
+                    None => {
+                        let _ = write!(html, "<td class=\"{}\" data-v=\"\">-</td>", class);
+                    }
+                }
+                match diff.and_then(|d| d.ratio()) {
+                    Some(r) => {
+                        let _ = write!(html, "<td data-v=\"{}\">{:.2}%</td>", r, r * 100.0);
+                    }
+                    None => html.push_str("<td data-v=\"\">-</td>"),
+                }
+            }
+            let rel = self.best.relative(c, objective);
+            let _ = write!(html, "<td data-v=\"{}\">{}</td>", rel, format_with_commas(rel));
+            let _ = write!(html, "<td>{}</td>", escape(&c.verdict.to_string()));
+            let _ = write!(html, "<td data-v=\"{}\">{:.3}</td>", c.elapsed, c.elapsed);
+            match c.usage {
+                Some(u) => {
+                    let _ = write!(html, "<td data-v=\"{0}\">{0:.3}</td><td data-v=\"{1}\">{1:.1}</td>", u.cpu_time(), u.max_rss_mib());
+                }
+                None => html.push_str("<td data-v=\"\">-</td><td data-v=\"\">-</td>"),
+            }
+            let mut comments = escape(&c.comments);
+            if let Some(p) = &c.panic_message {
+                let _ = write!(comments, "<br><span class=\"worse\">PANIC {}</span>", escape(p));
+            }
+            let _ = write!(html, "<td>{}</td>", comments);
+            html.push_str(&self.files_cell(c));
+            html.push_str("</tr>\n");
+        }
+        html.push_str("</tbody>\n</table>\n");
+        html
+    }
+
+    fn files_cell(&self, c: &crate::CaseResult) -> String {
+        let mut links = Vec::new();
+        for (label, path) in [("in", &c.inf), ("out", &c.outf), ("err", &c.errf)] {
+            // 入力ファイルは実行で上書きされない
+            if !path.is_empty() && (self.latest || label == "in") {
+                links.push(self.link(label, Path::new(path)));
+            }
+        }
+        for path in self.artifacts.get(&c.case).into_iter().flatten() {
+            let label = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            links.push(self.link(&label, path));
+        }
+        format!("<td class=\"files\">{}</td>", links.join(" "))
+    }
+
+    fn link(&self, label: &str, path: &Path) -> String {
+        format!("<a href=\"{}\">{}</a>", escape(&relative_link(&self.base_dir, path)), escape(label))
+    }
+
+    /// 横軸にベースライン、縦軸に今回のスコアをとった散布図。対角線より上が改善 (最大化の場合)。
+    fn scatter_svg(&self, baseline: &Baseline) -> String {
+        let points: Vec<(u32, f64, f64, std::cmp::Ordering)> = self
+            .record
+            .cases
+            .iter()
+            .filter_map(|c| {
+                let d = baseline.diff(c)?;
+                Some((c.case, d.base?.as_f64(), d.current?.as_f64(), d.ordering))
+            })
+            .collect();
+        let mut svg = chart_header(&format!("Score vs baseline {}", baseline.id));
+        if points.is_empty() {
+            svg.push_str("<text x=\"240\" y=\"110\" text-anchor=\"middle\">no comparable cases</text></svg>\n");
+            return svg;
+        }
+        let lo = points.iter().map(|p| p.1.min(p.2)).fold(f64::INFINITY, f64::min);
+        let hi = points.iter().map(|p| p.1.max(p.2)).fold(f64::NEG_INFINITY, f64::max);
+        let span = if hi > lo { hi - lo } else { 1.0 };
+        let x = |v: f64| PLOT_LEFT + (v - lo) / span * PLOT_WIDTH;
+        let y = |v: f64| PLOT_BOTTOM - (v - lo) / span * PLOT_HEIGHT;
+        let _ = write!(
+            svg,
+            "<line class=\"diag\" x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\"/>",
+            x(lo),
+            y(lo),
+            x(hi),
+            y(hi)
+        );
+        for (case, base, cur, ordering) in points {
+            let class = match ordering {
+                std::cmp::Ordering::Greater => "better",
+                std::cmp::Ordering::Less => "worse",
+                std::cmp::Ordering::Equal => "draw",
+            };
+            let _ = write!(
+                svg,
+                "<circle class=\"{}\" cx=\"{:.1}\" cy=\"{:.1}\" r=\"3\"><title>{:04}: {} → {}</title></circle>",
+                class,
+                x(base),
+                y(cur),
+                case,
+                base,
+                cur
+            );
+        }
+        svg.push_str(&axis_labels(lo, hi));
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+const CHART_WIDTH: f64 = 480.0;
+const CHART_HEIGHT: f64 = 220.0;
+const PLOT_LEFT: f64 = 50.0;
+const PLOT_BOTTOM: f64 = 190.0;
+const PLOT_WIDTH: f64 = CHART_WIDTH - PLOT_LEFT - 10.0;
+const PLOT_HEIGHT: f64 = PLOT_BOTTOM - 30.0;
+
+fn chart_header(title: &str) -> String {
+    format!(
+        "<svg width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\"><text class=\"title\" x=\"{cx}\" y=\"16\" text-anchor=\"middle\">{t}</text>\
+         <line class=\"axis\" x1=\"{l}\" y1=\"{b}\" x2=\"{r}\" y2=\"{b}\"/><line class=\"axis\" x1=\"{l}\" y1=\"30\" x2=\"{l}\" y2=\"{b}\"/>",
+        w = CHART_WIDTH,
+        h = CHART_HEIGHT,
+        cx = CHART_WIDTH / 2.0,
+        t = escape(title),
+        l = PLOT_LEFT,
+        r = PLOT_LEFT + PLOT_WIDTH,
+        b = PLOT_BOTTOM,
+    )
+}
+
+fn axis_labels(lo: f64, hi: f64) -> String {
+    format!(
+        "<text x=\"{l}\" y=\"{y}\">{lo}</text><text x=\"{r}\" y=\"{y}\" text-anchor=\"end\">{hi}</text>",
+        l = PLOT_LEFT,
+        r = PLOT_LEFT + PLOT_WIDTH,
+        y = PLOT_BOTTOM + 16.0,
+        lo = short_number(lo),
+        hi = short_number(hi),
+    )
+}
+
+fn short_number(v: f64) -> String {
+    if v.fract() == 0.0 && v.abs() < 1e15 {
+        format_with_commas(v as i64)
+    } else {
+        format!("{:.3}", v)
+    }
+}
+
+/// 値を等幅の区間に分けて数える。全て同じ値なら1区間。
+fn histogram(values: &[f64], bins: usize) -> (f64, f64, Vec<usize>) {
+    let lo = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let hi = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let bins = if hi > lo { bins.min(values.len()).max(1) } else { 1 };
+    let mut counts = vec![0; bins];
+    for &v in values {
+        let i = if hi > lo { ((v - lo) / (hi - lo) * bins as f64) as usize } else { 0 };
+        counts[i.min(bins - 1)] += 1;
+    }
+    (lo, hi, counts)
+}
+
+fn histogram_svg(title: &str, values: &[f64]) -> String {
+    let mut svg = chart_header(title);
+    if values.is_empty() {
+        svg.push_str("<text x=\"240\" y=\"110\" text-anchor=\"middle\">no data</text></svg>\n");
+        return svg;
+    }
+    let (lo, hi, counts) = histogram(values, HISTOGRAM_BINS);
+    let max = counts.iter().copied().max().unwrap_or(1).max(1) as f64;
+    let width = PLOT_WIDTH / counts.len() as f64;
+    let step = (hi - lo) / counts.len() as f64;
+    for (i, &n) in counts.iter().enumerate() {
+        let h = n as f64 / max * PLOT_HEIGHT;
+        let _ = write!(
+            svg,
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\"><title>{} - {}: {}</title></rect>",
+            PLOT_LEFT + i as f64 * width + 1.0,
+            PLOT_BOTTOM - h,
+            (width - 2.0).max(1.0),
+            h,
+            short_number(lo + step * i as f64),
+            short_number(lo + step * (i + 1) as f64),
+            n
+        );
+    }
+    svg.push_str(&axis_labels(lo, hi));
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn score_cell(score: Option<Score>) -> String {
+    match score {
+        Some(s) => format!("<td data-v=\"{}\">{}</td>", s.as_f64(), format_with_commas(s)),
+        None => "<td data-v=\"-Infinity\">-</td>".to_string(),
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// カレントディレクトリを基準に絶対パスにし、"." と ".." を取り除く。
+fn normalize(path: &Path) -> PathBuf {
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir().unwrap_or_default().join(path)
+    };
+    let mut ret = PathBuf::new();
+    for c in path.components() {
+        match c {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                ret.pop();
+            }
+            c => ret.push(c),
+        }
+    }
+    ret
+}
+
+/// `from_dir` から `to` への相対パス ("/" 区切り)。
+fn relative_link(from_dir: &Path, to: &Path) -> String {
+    let from = normalize(from_dir);
+    let to = normalize(to);
+    let from: Vec<_> = from.components().collect();
+    let to: Vec<_> = to.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut parts: Vec<String> = vec!["..".to_string(); from.len() - common];
+    parts.extend(to[common..].iter().map(|c| c.as_os_str().to_string_lossy().to_string()));
+    parts.join("/")
+}
+
+const STYLE: &str = "body{font-family:sans-serif;margin:1.5em;color:#222}\
+table{border-collapse:collapse;font-size:13px}th,td{border:1px solid #ccc;padding:2px 6px;text-align:right}\
+th{background:#eee;cursor:pointer;position:sticky;top:0}td:nth-last-child(-n+2){text-align:left}\
+tr.failed{background:#fdd}.note{color:#a60}.better{color:#080;fill:#080}.worse{color:#c00;fill:#c00}.draw{fill:#888}\
+.summary{display:flex;flex-wrap:wrap;gap:8px}.summary div{border:1px solid #ccc;padding:4px 10px}\
+dt{font-size:11px;color:#666}dd{margin:0;font-weight:bold}.charts{display:flex;flex-wrap:wrap;gap:16px;margin:1em 0}\
+svg{border:1px solid #ddd}rect{fill:#4a90d9}.axis{stroke:#444}.diag{stroke:#aaa;stroke-dasharray:4}\
+svg text{font-size:11px}.title{font-weight:bold}";
+
+/// 見出しをクリックすると data-v (無ければ表示文字列) で並べ替える。
+const SCRIPT: &str = "document.querySelectorAll('th').forEach((th,i)=>th.addEventListener('click',()=>{\
+const tb=th.closest('table').tBodies[0];const asc=th.dataset.asc!=='1';th.dataset.asc=asc?'1':'0';\
+const key=r=>{const c=r.cells[i];const v=c.dataset.v!==undefined?c.dataset.v:c.textContent;const n=parseFloat(v);return isNaN(n)?v:n;};\
+[...tb.rows].sort((a,b)=>{const x=key(a),y=key(b);\
+const d=typeof x==='number'&&typeof y==='number'?x-y:String(x).localeCompare(String(y));return asc?d:-d;})\
+.forEach(r=>tb.appendChild(r));}));";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CaseResult, Config, Objective};
+
+    #[test]
+    fn test_relative_link() {
+        assert_eq!(relative_link(Path::new("/a/out/report"), Path::new("/a/out/0001.txt")), "../0001.txt");
+        assert_eq!(relative_link(Path::new("/a/out/report"), Path::new("/a/in/./0001.txt")), "../../in/0001.txt");
+    }
+
+    #[test]
+    fn test_histogram() {
+        let (lo, hi, counts) = histogram(&[0.0, 1.0, 2.0, 10.0], 5);
+        assert_eq!((lo, hi), (0.0, 10.0));
+        assert_eq!(counts, vec![3, 0, 0, 1]);
+        assert_eq!(histogram(&[5.0, 5.0], 5).2, vec![2]);
+    }
+
+    #[test]
+    fn test_render() {
+        let config = Config::default_config();
+        let mut ok = CaseResult::ok_for_test(1, 100);
+        ok.comments = "<b>".to_string();
+        ok.inf = "/tmp/in/0001.txt".to_string();
+        ok.outf = "/tmp/out/0001.txt".to_string();
+        let failed = CaseResult::failed_for_test(2);
+        let mut record = RunRecord::new(&config, vec![ok, failed]);
+        record.id = "20240101-000000".to_string();
+
+        let mut base = RunRecord::new(&config, record.cases.clone());
+        base.cases[0].score = Some(Score::Int(80));
+        let baseline = Baseline::from_record(&base, Objective::Max);
+
+        let best = BestScores::default();
+        let summary = Summary::new(&record.cases, &best, Objective::Max, config.test.score_type);
+        let report = Report {
+            record: &record,
+            summary: &summary,
+            baseline: Some(&baseline),
+            best: &best,
+            base_dir: PathBuf::from("/tmp"),
+            artifacts: BTreeMap::from([(1, vec![PathBuf::from("/tmp/vis/0001.svg")])]),
+            latest: true,
+        };
+        let html = report.render();
+        assert!(html.contains("<td>0001</td>"));
+        assert!(html.contains("<tr class=\"failed\"><td>0002</td>"));
+        assert!(html.contains("&lt;b&gt;"));
+        assert!(html.contains("class=\"better\" data-v=\"20\">20</td>"));
+        assert!(html.contains("<a href=\"vis/0001.svg\">0001.svg</a>"));
+        assert!(html.contains("Score vs baseline"));
+        assert!(!html.contains("class=\"note\""));
+
+        // 古い実行では上書きされたファイルにリンクしない
+        let old = Report { artifacts: BTreeMap::new(), latest: false, ..report };
+        let html = old.render();
+        assert!(html.contains("class=\"note\""));
+        assert!(!html.contains(">out</a>"));
+        assert!(html.contains(">in</a>"));
+    }
+
+    #[test]
+    fn test_artifact_case() {
+        assert_eq!(artifact_case("0003.svg"), Some(3));
+        assert_eq!(artifact_case("00031.svg"), Some(31));
+        assert_eq!(artifact_case("10000_a.png"), Some(10000));
+        assert_eq!(artifact_case("vis.html"), None);
+    }
+}
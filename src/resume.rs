@@ -0,0 +1,113 @@
+use std::collections::BTreeMap;
+use std::io;
+
+use crate::best::BestScores;
+use crate::history::{self, RunRecord};
+use crate::{hash, CaseResult, Heu, Summary, TestConfig};
+
+/// 結果に影響する設定が同じか。ケース指定・並列数・出力形式などは比べない。
+pub fn same_setup(a: &TestConfig, b: &TestConfig) -> bool {
+    a.bin == b.bin
+        && a.use_tester == b.use_tester
+        && (!a.use_tester || a.tester == b.tester)
+        && a.in_dir == b.in_dir
+        && a.vis == b.vis
+        && a.score_regex == b.score_regex
+        && a.score_type == b.score_type
+        && a.time_limit == b.time_limit
+        && a.no_evaluate == b.no_evaluate
+}
+
+/// 引き継ぐ結果と今回の結果を1つにする。同じケースがあれば今回の結果を使う。
+/// 引き継ぐ結果の順に並べ、その後に今回だけのケースを続ける。
+pub fn merge_results(reused: Vec<CaseResult>, results: Vec<CaseResult>) -> Vec<CaseResult> {
+    let mut new: BTreeMap<u32, CaseResult> = results.iter().map(|r| (r.case, r.clone())).collect();
+    let mut merged: Vec<CaseResult> = reused.into_iter().map(|r| new.remove(&r.case).unwrap_or(r)).collect();
+    merged.extend(results.into_iter().filter(|r| new.contains_key(&r.case)));
+    merged
+}
+
+impl Heu {
+    /// `cases` だけを実行する Heu。
+    fn with_cases(&self, cases: Vec<u32>) -> Heu {
+        self.with_config_and_cases(self.config.clone(), cases)
+    }
+
+    /// 同じバイナリ (ハッシュが同じ) と設定での直近の実行。ビルドしてから呼ぶ。
+    fn latest_same_run(&self, what: &str) -> io::Result<RunRecord> {
+        let bin_hash = hash::command_hash(&self.config.test.bin)?;
+        history::load_all(&self.config.test.out_dir)?
+            .into_iter()
+            .rev()
+            .find(|r| r.bin_hash.as_deref() == Some(bin_hash.as_str()) && same_setup(&r.config.test, &self.config.test))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no previous run with the same binary and config to {}", what),
+                )
+            })
+    }
+
+    /// 同じバイナリ (ハッシュが同じ) と設定での直近の実行で終わっているケースを飛ばして残りを実行し、
+    /// 終わっていたケースの結果と合わせて1つの実行記録にする。
+    pub fn resume(&self) -> io::Result<Summary> {
+        self.prepare()?;
+        let prev = self.latest_same_run("resume")?;
+        let done: BTreeMap<u32, &CaseResult> = prev.cases.iter().map(|r| (r.case, r)).collect();
+        let reused: Vec<CaseResult> = self.cases.iter().filter_map(|c| done.get(c).map(|r| (*r).clone())).collect();
+        let remaining: Vec<u32> = self.cases.iter().copied().filter(|c| !done.contains_key(c)).collect();
+        eprintln!("Resuming run {}: {} cases done, {} remaining", prev.id, reused.len(), remaining.len());
+        self.with_cases(remaining).run_and_save(reused, Some(&prev.id))
+    }
+
+    /// 同じバイナリ (ハッシュが同じ) と設定での直近の実行で失敗したケースだけを実行し直し、
+    /// その実行の他のケースの結果と合わせて1つの実行記録にする。
+    /// `selected` ならそのうちケース指定に含まれるものだけ、そうでなければ失敗した全てのケースを対象にする。
+    pub fn rerun_failed(&self, selected: bool) -> io::Result<Summary> {
+        self.prepare()?;
+        let prev = self.latest_same_run("rerun")?;
+        let failed: Vec<u32> = prev
+            .cases
+            .iter()
+            .filter(|r| !r.verdict.is_ok() && (!selected || self.cases.contains(&r.case)))
+            .map(|r| r.case)
+            .collect();
+        if failed.is_empty() {
+            eprintln!("No failed cases to rerun in run {}", prev.id);
+            let best = BestScores::load(&self.config.test.out_dir)?;
+            return Ok(Summary::new(&prev.cases, &best, self.config.test.objective, self.config.test.score_type));
+        }
+        eprintln!("Rerunning {} failed cases of run {}", failed.len(), prev.id);
+        self.with_cases(failed).run_and_save(prev.cases, Some(&prev.id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Config, Score};
+
+    #[test]
+    fn test_merge_results() {
+        let reused = vec![CaseResult::ok_for_test(3, 1), CaseResult::failed_for_test(1), CaseResult::ok_for_test(2, 5)];
+        let merged = merge_results(reused, vec![CaseResult::ok_for_test(1, 7), CaseResult::ok_for_test(9, 2)]);
+        let got: Vec<(u32, Option<Score>)> = merged.iter().map(|r| (r.case, r.ok_score())).collect();
+        assert_eq!(
+            got,
+            vec![(3, Some(Score::Int(1))), (1, Some(Score::Int(7))), (2, Some(Score::Int(5))), (9, Some(Score::Int(2)))]
+        );
+    }
+
+    #[test]
+    fn test_same_setup() {
+        let a = Config::default_config().test;
+        let mut b = a.clone();
+        b.cases = "0-99".to_string();
+        b.threads = 1;
+        assert!(same_setup(&a, &b));
+        b.tester = "other".to_string();
+        assert!(same_setup(&a, &b));
+        b.time_limit = Some(2.0);
+        assert!(!same_setup(&a, &b));
+    }
+}
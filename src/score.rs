@@ -0,0 +1,154 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, Sub};
+
+/// スコアの数値型。
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScoreType {
+    /// 符号付き整数。
+    #[default]
+    Int,
+    /// 浮動小数点数。
+    Float,
+}
+
+impl fmt::Display for ScoreType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScoreType::Int => write!(f, "int"),
+            ScoreType::Float => write!(f, "float"),
+        }
+    }
+}
+
+/// 1ケース (または合計) のスコア。
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Score {
+    Int(i64),
+    Float(f64),
+}
+
+impl Score {
+    pub fn zero(ty: ScoreType) -> Self {
+        match ty {
+            ScoreType::Int => Score::Int(0),
+            ScoreType::Float => Score::Float(0.0),
+        }
+    }
+
+    /// 文字列を指定した型のスコアとしてパースする。
+    pub fn parse(s: &str, ty: ScoreType) -> Option<Self> {
+        let s = s.trim();
+        match ty {
+            ScoreType::Int => s.parse().ok().map(Score::Int),
+            ScoreType::Float => s.parse().ok().filter(|v: &f64| v.is_finite()).map(Score::Float),
+        }
+    }
+
+    pub fn as_f64(self) -> f64 {
+        match self {
+            Score::Int(v) => v as f64,
+            Score::Float(v) => v,
+        }
+    }
+
+    /// 大小比較。整数同士は厳密に、それ以外は f64 で比較する。
+    pub fn cmp_value(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Score::Int(a), Score::Int(b)) => a.cmp(b),
+            _ => self.as_f64().total_cmp(&other.as_f64()),
+        }
+    }
+
+    /// 平均値。空なら None。整数スコアの平均も小数になるので f64 で返す。
+    pub fn mean<'a>(scores: impl IntoIterator<Item = &'a Score>) -> Option<f64> {
+        let (sum, n) = scores.into_iter().fold((0.0, 0usize), |(s, n), v| (s + v.as_f64(), n + 1));
+        (n > 0).then(|| sum / n as f64)
+    }
+}
+
+impl Add for Score {
+    type Output = Score;
+
+    fn add(self, rhs: Score) -> Score {
+        match (self, rhs) {
+            (Score::Int(a), Score::Int(b)) => Score::Int(a.saturating_add(b)),
+            _ => Score::Float(self.as_f64() + rhs.as_f64()),
+        }
+    }
+}
+
+impl Sub for Score {
+    type Output = Score;
+
+    fn sub(self, rhs: Score) -> Score {
+        match (self, rhs) {
+            (Score::Int(a), Score::Int(b)) => Score::Int(a.saturating_sub(b)),
+            _ => Score::Float(self.as_f64() - rhs.as_f64()),
+        }
+    }
+}
+
+impl<'a> Sum<&'a Score> for Score {
+    fn sum<I: Iterator<Item = &'a Score>>(iter: I) -> Score {
+        iter.fold(Score::Int(0), |acc, v| acc + *v)
+    }
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Score::Int(v) => write!(f, "{}", v),
+            // 0.1 + 0.2 のような誤差を表示しないよう小数点以下6桁で丸める
+            Score::Float(v) => {
+                let s = format!("{:.6}", v);
+                let s = s.trim_end_matches('0').trim_end_matches('.');
+                write!(f, "{}", if s == "-0" { "0" } else { s })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Score::parse("-42", ScoreType::Int), Some(Score::Int(-42)));
+        assert_eq!(Score::parse("1.5", ScoreType::Int), None);
+        assert_eq!(Score::parse("1.5", ScoreType::Float), Some(Score::Float(1.5)));
+        assert_eq!(Score::parse("nan", ScoreType::Float), None);
+        assert_eq!(Score::parse("abc", ScoreType::Float), None);
+    }
+
+    #[test]
+    fn test_sum_and_mean() {
+        let ints = [Score::Int(3), Score::Int(-5)];
+        assert_eq!(ints.iter().sum::<Score>(), Score::Int(-2));
+        assert_eq!(Score::mean(&ints), Some(-1.0));
+        let floats = [Score::Float(0.5), Score::Float(0.25)];
+        assert_eq!(floats.iter().sum::<Score>(), Score::Float(0.75));
+        assert_eq!(Score::mean(&[]), None);
+        assert_eq!(Score::Int(2).cmp_value(&Score::Float(2.5)), Ordering::Less);
+    }
+
+    #[test]
+    fn test_serde_roundtrip() {
+        let json = serde_json::to_string(&[Score::Int(3), Score::Float(2.5)]).unwrap();
+        assert_eq!(json, "[3,2.5]");
+        let back: Vec<Score> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, vec![Score::Int(3), Score::Float(2.5)]);
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Score::Float(0.1 + 0.2).to_string(), "0.3");
+        assert_eq!(Score::Float(100.0).to_string(), "100");
+        assert_eq!(Score::Int(-7).to_string(), "-7");
+    }
+}
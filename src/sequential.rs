@@ -0,0 +1,125 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::stats::{mean, wilcoxon_signed_rank, SIGNIFICANCE_LEVEL};
+
+/// `[sequential]`: ベースラインとの差が決まった時点で実行を打ち切る設定。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SequentialConfig {
+    /// ケースを `batch` 件ずつ実行し、1回ごとにベースラインとの差を検定する。
+    pub enable: bool,
+    /// 1回に実行するケース数。
+    pub batch: usize,
+    /// 比較できたケースがこれより少ないうちは打ち切らない。
+    pub min_cases: usize,
+}
+
+impl Default for SequentialConfig {
+    fn default() -> Self {
+        Self { enable: false, batch: 100, min_cases: 20 }
+    }
+}
+
+/// ベースラインとの比較の判定。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Improved,
+    Regressed,
+    /// 予算のケースを使い切っても差が決まらなかった。
+    Undecided,
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Decision::Improved => write!(f, "IMPROVED"),
+            Decision::Regressed => write!(f, "REGRESSED"),
+            Decision::Undecided => write!(f, "UNDECIDED"),
+        }
+    }
+}
+
+/// 逐次検定。途中で何度も検定すると偶然の差で打ち切りやすくなるので、
+/// 有意水準を予定している検定の回数で割る (Bonferroni)。
+#[derive(Debug, Clone, PartialEq)]
+pub struct SequentialTest {
+    pub batch: usize,
+    /// 予算のケースを全て実行するまでの検定の回数。
+    pub looks: usize,
+    pub min_cases: usize,
+}
+
+impl SequentialTest {
+    /// `budget` ケースを `config.batch` 件ずつ実行する場合の検定。
+    pub fn new(config: &SequentialConfig, budget: usize) -> Self {
+        let batch = config.batch.max(1);
+        Self { batch, looks: budget.div_ceil(batch).max(1), min_cases: config.min_cases }
+    }
+
+    /// 1回の検定で使う有意水準。
+    pub fn alpha(&self) -> f64 {
+        SIGNIFICANCE_LEVEL / self.looks as f64
+    }
+
+    /// ケースごとの相対スコアの差 (今回 - ベースライン) から判定する。
+    pub fn decide(&self, diffs: &[f64]) -> Decision {
+        if diffs.len() < self.min_cases {
+            return Decision::Undecided;
+        }
+        match (wilcoxon_signed_rank(diffs), mean(diffs)) {
+            (Some(w), Some(m)) if w.p_value < self.alpha() && m > 0.0 => Decision::Improved,
+            (Some(w), Some(m)) if w.p_value < self.alpha() && m < 0.0 => Decision::Regressed,
+            _ => Decision::Undecided,
+        }
+    }
+}
+
+/// 逐次実行の結果。
+#[derive(Debug, Clone, PartialEq)]
+pub struct SequentialResult {
+    pub decision: Decision,
+    /// 実行したケース数。
+    pub cases: usize,
+    /// 予算のケース数。
+    pub budget: usize,
+    /// 実行したバッチの数。
+    pub batches: usize,
+}
+
+impl SequentialResult {
+    /// "SEQUENTIAL[CASES=300/2000 BATCHES=3 ALPHA=0.0025] IMPROVED" の形式。
+    pub fn format(&self, test: &SequentialTest) -> String {
+        format!(
+            "SEQUENTIAL[CASES={}/{} BATCHES={} ALPHA={:.4}] {}",
+            self.cases,
+            self.budget,
+            self.batches,
+            test.alpha(),
+            self.decision
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sequential_decide() {
+        let config = SequentialConfig { enable: true, batch: 100, min_cases: 20 };
+        let test = SequentialTest::new(&config, 1000);
+        assert_eq!(test.looks, 10);
+        assert!((test.alpha() - 0.005).abs() < 1e-12);
+        assert_eq!(SequentialTest::new(&config, 1001).looks, 11);
+
+        // 少なすぎるうちは打ち切らない
+        assert_eq!(test.decide(&[0.01; 10]), Decision::Undecided);
+        let up: Vec<f64> = (0..40).map(|i| 0.01 + i as f64 * 1e-4).collect();
+        assert_eq!(test.decide(&up), Decision::Improved);
+        let down: Vec<f64> = up.iter().map(|d| -d).collect();
+        assert_eq!(test.decide(&down), Decision::Regressed);
+        let mixed: Vec<f64> = up.iter().enumerate().map(|(i, d)| if i % 2 == 0 { *d } else { -d }).collect();
+        assert_eq!(test.decide(&mixed), Decision::Undecided);
+    }
+}
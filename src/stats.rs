@@ -0,0 +1,343 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+
+use crate::best::BestScores;
+use crate::{CaseResult, Objective, Score, ScoreType};
+
+/// ケースとそのスコア。最小・最大のケースを示すのに使う。
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct CaseScore {
+    pub case: u32,
+    pub score: Score,
+}
+
+/// 1回の実行全体の集計。スコアの統計量は成功したケースだけで計算する。
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Summary {
+    /// 実行したケース数。
+    pub cases: usize,
+    /// 失敗したケース番号。出力では件数を `failed`、番号の列を `failed_cases` とする ([`crate::output`])。
+    #[serde(rename = "failed_cases")]
+    pub failed: Vec<u32>,
+    pub total: Score,
+    /// AHC と同じ相対スコアの合計。
+    pub relative_total: u64,
+    pub mean: Option<f64>,
+    pub median: Option<f64>,
+    /// 標本標準偏差。成功したケースが2つ未満なら None。
+    pub stddev: Option<f64>,
+    pub min: Option<CaseScore>,
+    pub max: Option<CaseScore>,
+    /// 幾何平均。0 以下のスコアがあれば None。
+    pub geometric_mean: Option<f64>,
+    /// 自然対数のスコアの合計。0 以下のスコアがあれば None。
+    pub log_score_sum: Option<f64>,
+    pub max_elapsed: f64,
+    pub p95_elapsed: f64,
+    pub mean_elapsed: f64,
+    /// ベースラインとの比較の有意性。ベースラインが無ければ None。
+    pub significance: Option<Significance>,
+}
+
+impl Summary {
+    pub fn new(results: &[CaseResult], best: &BestScores, objective: Objective, score_type: ScoreType) -> Self {
+        let scored: Vec<CaseScore> = results
+            .iter()
+            .filter_map(|r| r.ok_score().map(|score| CaseScore { case: r.case, score }))
+            .collect();
+        let values: Vec<f64> = scored.iter().map(|s| s.score.as_f64()).collect();
+        let logs: Option<Vec<f64>> = values.iter().map(|&v| (v > 0.0).then(|| v.ln())).collect();
+        let elapsed: Vec<f64> = results.iter().map(|r| r.elapsed).collect();
+
+        Self {
+            cases: results.len(),
+            failed: results.iter().filter(|r| !r.verdict.is_ok()).map(|r| r.case).collect(),
+            total: scored.iter().fold(Score::zero(score_type), |acc, s| acc + s.score),
+            relative_total: results.iter().map(|r| best.relative(r, objective)).sum(),
+            mean: mean(&values),
+            median: percentile(&values, 0.5),
+            stddev: stddev(&values),
+            // 同じスコアのケースが複数あれば番号の小さい方
+            min: scored.iter().copied().reduce(|a, b| if b.score.cmp_value(&a.score).is_lt() { b } else { a }),
+            max: scored.iter().copied().reduce(|a, b| if b.score.cmp_value(&a.score).is_gt() { b } else { a }),
+            geometric_mean: logs.as_deref().and_then(mean).map(f64::exp),
+            log_score_sum: logs.filter(|l| !l.is_empty()).map(|l| l.iter().sum()),
+            max_elapsed: elapsed.iter().copied().fold(0.0, f64::max),
+            p95_elapsed: nearest_rank(&elapsed, 0.95).unwrap_or(0.0),
+            mean_elapsed: mean(&elapsed).unwrap_or(0.0),
+            significance: None,
+        }
+    }
+}
+
+pub fn mean(values: &[f64]) -> Option<f64> {
+    (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+}
+
+/// 標本標準偏差 (n-1 で割る)。
+pub fn stddev(values: &[f64]) -> Option<f64> {
+    if values.len() < 2 {
+        return None;
+    }
+    let m = mean(values)?;
+    let var = values.iter().map(|v| (v - m).powi(2)).sum::<f64>() / (values.len() - 1) as f64;
+    Some(var.sqrt())
+}
+
+/// 線形補間したパーセンタイル (p は 0.0〜1.0)。p = 0.5 で中央値。
+pub fn percentile(values: &[f64], p: f64) -> Option<f64> {
+    let sorted = sorted(values)?;
+    let pos = p.clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let (lo, hi) = (pos.floor() as usize, pos.ceil() as usize);
+    Some(sorted[lo] + (sorted[hi] - sorted[lo]) * (pos - lo as f64))
+}
+
+/// 最近傍順位法のパーセンタイル。実際に観測した値のいずれかを返す。
+pub fn nearest_rank(values: &[f64], p: f64) -> Option<f64> {
+    let sorted = sorted(values)?;
+    let rank = (p.clamp(0.0, 1.0) * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+/// 有意とみなす p 値の上限。
+pub const SIGNIFICANCE_LEVEL: f64 = 0.05;
+
+/// Wilcoxon の符号付き順位検定の結果。
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Wilcoxon {
+    /// 0 でない差の数。
+    pub n: usize,
+    /// 正の差の順位和。
+    pub w_plus: f64,
+    pub z: f64,
+    /// 両側検定の p 値。
+    pub p_value: f64,
+}
+
+impl Wilcoxon {
+    pub fn is_significant(&self) -> bool {
+        self.p_value < SIGNIFICANCE_LEVEL
+    }
+}
+
+/// 対応のある差 `diffs` の中央値が 0 かどうかを Wilcoxon の符号付き順位検定で調べる。
+/// 差が 0 のものは除き、正規近似 (同順位と連続性の補正あり) で p 値を求める。差が全て 0 なら None。
+pub fn wilcoxon_signed_rank(diffs: &[f64]) -> Option<Wilcoxon> {
+    let nonzero: Vec<f64> = diffs.iter().copied().filter(|&d| d != 0.0).collect();
+    let n = nonzero.len();
+    if n == 0 {
+        return None;
+    }
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|&i, &j| nonzero[i].abs().total_cmp(&nonzero[j].abs()));
+    let mut ranks = vec![0.0; n];
+    let mut ties = 0.0;
+    let mut i = 0;
+    while i < n {
+        let mut j = i;
+        while j + 1 < n && nonzero[order[j + 1]].abs() == nonzero[order[i]].abs() {
+            j += 1;
+        }
+        // 同順位には平均の順位を付ける
+        let rank = (i + j) as f64 / 2.0 + 1.0;
+        for &k in &order[i..=j] {
+            ranks[k] = rank;
+        }
+        let t = (j - i + 1) as f64;
+        ties += t * t * t - t;
+        i = j + 1;
+    }
+    let w_plus: f64 = nonzero.iter().zip(&ranks).filter(|(d, _)| **d > 0.0).map(|(_, r)| r).sum();
+
+    let nf = n as f64;
+    let mean = nf * (nf + 1.0) / 4.0;
+    let var = nf * (nf + 1.0) * (2.0 * nf + 1.0) / 24.0 - ties / 48.0;
+    // 連続性の補正
+    let dev = w_plus - mean;
+    let corrected = (dev.abs() - 0.5).max(0.0).copysign(dev);
+    let z = if var > 0.0 { corrected / var.sqrt() } else { 0.0 };
+    let p_value = (2.0 * (1.0 - normal_cdf(z.abs()))).min(1.0);
+    Some(Wilcoxon { n, w_plus, z, p_value })
+}
+
+/// ブートストラップの再標本化の回数。
+const BOOTSTRAP_RESAMPLES: usize = 2000;
+
+/// 平均の信頼区間をブートストラップ (百分位数法) で求める。`confidence` は 0.95 など。
+/// 同じ入力なら同じ結果になるよう乱数のシードは固定する。
+pub fn bootstrap_mean_ci(values: &[f64], confidence: f64) -> Option<(f64, f64)> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut rng = StdRng::seed_from_u64(0);
+    let n = values.len();
+    let means: Vec<f64> = (0..BOOTSTRAP_RESAMPLES)
+        .map(|_| (0..n).map(|_| values[rng.gen_range(0..n)]).sum::<f64>() / n as f64)
+        .collect();
+    let tail = (1.0 - confidence) / 2.0;
+    Some((percentile(&means, tail)?, percentile(&means, 1.0 - tail)?))
+}
+
+/// ベースラインとの対応のある比較。ケースごとの相対スコアの差 (今回 - ベースライン) を使う。
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Significance {
+    /// 相対スコアの差の平均。
+    pub mean_diff: f64,
+    /// 平均の 95% ブートストラップ信頼区間。
+    pub ci_low: f64,
+    pub ci_high: f64,
+    /// Wilcoxon の符号付き順位検定の p 値。差が全て 0 なら 1。
+    pub p_value: f64,
+}
+
+impl Significance {
+    /// ケースごとの差から求める。差が1つも無ければ None。
+    pub fn from_diffs(diffs: &[f64]) -> Option<Self> {
+        let mean_diff = mean(diffs)?;
+        let (ci_low, ci_high) = bootstrap_mean_ci(diffs, 1.0 - SIGNIFICANCE_LEVEL)?;
+        let p_value = wilcoxon_signed_rank(diffs).map_or(1.0, |w| w.p_value);
+        Some(Self { mean_diff, ci_low, ci_high, p_value })
+    }
+
+    pub fn is_significant(&self) -> bool {
+        self.p_value < SIGNIFICANCE_LEVEL
+    }
+
+    /// "REL_DIFF=+0.412% CI95=[+0.120%, +0.705%] P=0.0209" の形式。
+    pub fn format(&self) -> String {
+        format!(
+            "REL_DIFF={:+.3}% CI{}=[{:+.3}%, {:+.3}%] P={:.4}",
+            self.mean_diff * 100.0,
+            ((1.0 - SIGNIFICANCE_LEVEL) * 100.0).round(),
+            self.ci_low * 100.0,
+            self.ci_high * 100.0,
+            self.p_value
+        )
+    }
+}
+
+/// 標準正規分布の累積分布関数。
+pub fn normal_cdf(x: f64) -> f64 {
+    0.5 * erfc(-x / std::f64::consts::SQRT_2)
+}
+
+/// 相補誤差関数 (Chebyshev 近似、相対誤差 1.2e-7 以下)。
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = -1.26551223
+        + t * (1.00002368
+            + t * (0.37409196
+                + t * (0.09678418
+                    + t * (-0.18628806
+                        + t * (0.27886807 + t * (-1.13520398 + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277))))))));
+    let r = t * (-z * z + poly).exp();
+    if x >= 0.0 {
+        r
+    } else {
+        2.0 - r
+    }
+}
+
+fn sorted(values: &[f64]) -> Option<Vec<f64>> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    Some(sorted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_basic_stats() {
+        let v = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+        assert_eq!(mean(&v), Some(5.0));
+        assert_eq!(percentile(&v, 0.5), Some(4.5));
+        assert!((stddev(&v).unwrap() - 2.138089935).abs() < 1e-6);
+        assert_eq!(nearest_rank(&v, 0.95), Some(9.0));
+        assert_eq!(stddev(&[1.0]), None);
+        assert_eq!(percentile(&[], 0.5), None);
+    }
+
+    #[test]
+    fn test_summary() {
+        let results = vec![
+            CaseResult { elapsed: 0.1, ..CaseResult::ok_for_test(0, 100) },
+            CaseResult { elapsed: 0.3, ..CaseResult::ok_for_test(1, 400) },
+            CaseResult { elapsed: 2.0, ..CaseResult::failed_for_test(2) },
+            CaseResult { elapsed: 0.2, ..CaseResult::ok_for_test(3, 100) },
+        ];
+        let s = Summary::new(&results, &BestScores::default(), Objective::Max, ScoreType::Int);
+        assert_eq!(s.cases, 4);
+        assert_eq!(s.failed, vec![2]);
+        assert_eq!(s.total, Score::Int(600));
+        assert_eq!(s.relative_total, 3_000_000_000);
+        assert_eq!(s.median, Some(100.0));
+        assert_eq!(s.min, Some(CaseScore { case: 0, score: Score::Int(100) }));
+        assert_eq!(s.max, Some(CaseScore { case: 1, score: Score::Int(400) }));
+        assert!((s.geometric_mean.unwrap() - 158.740105).abs() < 1e-4);
+        assert!((s.log_score_sum.unwrap() - (100f64.ln() * 2.0 + 400f64.ln())).abs() < 1e-9);
+        assert_eq!(s.max_elapsed, 2.0);
+        assert_eq!(s.p95_elapsed, 2.0);
+
+        let with_zero = vec![CaseResult::ok_for_test(0, 0), CaseResult::ok_for_test(1, 5)];
+        let s = Summary::new(&with_zero, &BestScores::default(), Objective::Max, ScoreType::Int);
+        assert_eq!(s.geometric_mean, None);
+        assert_eq!(s.log_score_sum, None);
+    }
+
+    #[test]
+    fn test_normal_cdf() {
+        assert!((normal_cdf(0.0) - 0.5).abs() < 1e-7);
+        assert!((normal_cdf(1.959964) - 0.975).abs() < 1e-6);
+        assert!((normal_cdf(-1.0) - 0.158655).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_wilcoxon_signed_rank() {
+        // scipy.stats.wilcoxon(range(1, 11), correction=True, method="approx") と同じ値
+        let diffs: Vec<f64> = (1..=10).map(f64::from).collect();
+        let w = wilcoxon_signed_rank(&diffs).unwrap();
+        assert_eq!((w.n, w.w_plus), (10, 55.0));
+        assert!((w.p_value - 0.005922).abs() < 1e-5, "{:?}", w);
+        assert!(w.is_significant());
+
+        let negated: Vec<f64> = diffs.iter().map(|d| -d).collect();
+        let w2 = wilcoxon_signed_rank(&negated).unwrap();
+        assert_eq!(w2.w_plus, 0.0);
+        assert!((w2.p_value - w.p_value).abs() < 1e-12 && w2.z < 0.0);
+
+        // 0 は除き、同順位は平均の順位
+        let w = wilcoxon_signed_rank(&[0.0, 1.0, -1.0, 2.0]).unwrap();
+        assert_eq!((w.n, w.w_plus), (3, 4.5));
+        assert!(!w.is_significant());
+        assert_eq!(wilcoxon_signed_rank(&[0.0, 0.0]), None);
+    }
+
+    #[test]
+    fn test_bootstrap_and_significance() {
+        assert_eq!(bootstrap_mean_ci(&[0.5; 10], 0.95), Some((0.5, 0.5)));
+        assert_eq!(bootstrap_mean_ci(&[], 0.95), None);
+
+        let diffs: Vec<f64> = (0..50).map(|i| 0.01 + 0.001 * (i % 7) as f64 - 0.003).collect();
+        let (lo, hi) = bootstrap_mean_ci(&diffs, 0.95).unwrap();
+        let m = mean(&diffs).unwrap();
+        assert!(lo < m && m < hi && lo > 0.0, "{} {} {}", lo, m, hi);
+        assert_eq!(bootstrap_mean_ci(&diffs, 0.95), Some((lo, hi)));
+
+        let s = Significance::from_diffs(&diffs).unwrap();
+        assert!(s.is_significant());
+        assert!(s.format().starts_with("REL_DIFF=+0.994% CI95=["), "{}", s.format());
+
+        let noise: Vec<f64> = (0..40).map(|i| if i % 2 == 0 { 0.01 } else { -0.01 }).collect();
+        let s = Significance::from_diffs(&noise).unwrap();
+        assert!(!s.is_significant() && s.ci_low < 0.0 && s.ci_high > 0.0, "{:?}", s);
+        assert_eq!(Significance::from_diffs(&[0.0, 0.0]).map(|s| s.p_value), Some(1.0));
+        assert_eq!(Significance::from_diffs(&[]), None);
+    }
+}
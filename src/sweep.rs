@@ -0,0 +1,281 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, IsTerminal};
+
+use crate::best::BestScores;
+use crate::{format_with_commas, paint, Heu, Objective, Summary, GREEN};
+
+/// スイープするパラメータの値。環境変数として渡すときは文字列にする。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ParamValue {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Str(String),
+}
+
+impl fmt::Display for ParamValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamValue::Int(v) => write!(f, "{}", v),
+            ParamValue::Float(v) => write!(f, "{}", v),
+            ParamValue::Bool(v) => write!(f, "{}", v),
+            ParamValue::Str(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+impl ParamValue {
+    /// TOML の値として書く。
+    pub fn to_toml(&self) -> String {
+        match self {
+            ParamValue::Str(s) => format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"")),
+            ParamValue::Float(v) if v.fract() == 0.0 => format!("{:.1}", v),
+            v => v.to_string(),
+        }
+    }
+}
+
+/// 環境変数名と値の組。
+pub type Params = Vec<(String, String)>;
+
+/// `[sweep]` の設定。
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SweepConfig {
+    /// パラメータごとの値の候補。全ての組み合わせを試す。
+    #[serde(default)]
+    pub grid: BTreeMap<String, Vec<ParamValue>>,
+    /// 試す組み合わせを直接並べたもの。grid より先に試す。
+    #[serde(default)]
+    pub list: Vec<BTreeMap<String, ParamValue>>,
+}
+
+impl SweepConfig {
+    pub fn is_empty(&self) -> bool {
+        self.grid.is_empty() && self.list.is_empty()
+    }
+
+    /// 試す組み合わせを順に返す。list の各要素の後に grid の直積 (後のキーほど速く変わる) が続く。
+    pub fn combinations(&self) -> Vec<Params> {
+        let to_params =
+            |m: &BTreeMap<String, ParamValue>| m.iter().map(|(k, v)| (k.clone(), v.to_string())).collect::<Params>();
+        let mut ret: Vec<Params> = self.list.iter().map(to_params).collect();
+        if !self.grid.is_empty() {
+            let mut grid: Vec<Params> = vec![Vec::new()];
+            for (name, values) in &self.grid {
+                grid = grid
+                    .iter()
+                    .flat_map(|p| {
+                        values.iter().map(move |v| {
+                            let mut p = p.clone();
+                            p.push((name.clone(), v.to_string()));
+                            p
+                        })
+                    })
+                    .collect();
+            }
+            ret.extend(grid);
+        }
+        ret
+    }
+
+    /// `--param NAME=V1,V2,...` を grid に加える。同じ名前があれば置き換える。
+    pub fn add_param_arg(&mut self, arg: &str) -> io::Result<()> {
+        let invalid = || {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("invalid --param '{}': expected NAME=V1,V2,...", arg))
+        };
+        let (name, values) = arg.split_once('=').ok_or_else(invalid)?;
+        let name = name.trim();
+        if name.is_empty() || values.trim().is_empty() {
+            return Err(invalid());
+        }
+        let values = values.split(',').map(|v| ParamValue::Str(v.trim().to_string())).collect();
+        self.grid.insert(name.to_string(), values);
+        Ok(())
+    }
+}
+
+/// 1つの組み合わせの結果。
+pub struct SweepResult {
+    pub params: Params,
+    pub summary: Summary,
+}
+
+/// 失敗したケースが少ない方、同じならスコア合計が良い方を良いとする。
+fn is_better(a: &Summary, b: &Summary, objective: Objective) -> bool {
+    b.failed
+        .len()
+        .cmp(&a.failed.len())
+        .then_with(|| objective.compare(a.total, b.total))
+        .is_gt()
+}
+
+/// 最も良い組み合わせの位置。
+pub fn best_index(results: &[SweepResult], objective: Objective) -> Option<usize> {
+    (0..results.len()).reduce(|best, i| {
+        if is_better(&results[i].summary, &results[best].summary, objective) {
+            i
+        } else {
+            best
+        }
+    })
+}
+
+fn format_params(params: &Params) -> String {
+    params.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join(" ")
+}
+
+impl Heu {
+    /// `[sweep]` の組み合わせごとに全ケースを実行し、組み合わせごとの合計を表にして表示する。
+    /// パラメータは環境変数としてソリューションに渡す。実行履歴や最良スコアは更新しない。
+    /// 相対スコアは保存済みの最良スコアと全ての組み合わせの結果のうち最良のものを基準にする。
+    pub fn sweep(&self) -> io::Result<Vec<SweepResult>> {
+        let combinations = self.config.sweep.combinations();
+        if combinations.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no sweep parameters: add [sweep] to config or pass --param NAME=V1,V2",
+            ));
+        }
+        self.prepare()?;
+
+        let objective = self.config.test.objective;
+        let score_type = self.config.test.score_type;
+        let mut best = BestScores::load(&self.config.test.out_dir)?;
+        let total = combinations.len();
+        let mut runs = Vec::with_capacity(total);
+        for (i, params) in combinations.into_iter().enumerate() {
+            let mut cases = Vec::with_capacity(self.cases.len());
+            self.run_parallel(&self.cases, &params, |r| cases.push(r))?;
+            let summary = Summary::new(&cases, &best, objective, score_type);
+            eprintln!(
+                "[{}/{}] {} TOTAL={} FAILED={}/{}",
+                i + 1,
+                total,
+                format_params(&params),
+                format_with_commas(summary.total),
+                summary.failed.len(),
+                summary.cases
+            );
+            runs.push((params, cases));
+        }
+
+        // 相対スコアは全ての組み合わせの中での最良スコアを基準にする (保存はしない)
+        for (_, cases) in &runs {
+            best.update(cases, None, objective);
+        }
+        let results: Vec<SweepResult> = runs
+            .into_iter()
+            .map(|(params, cases)| SweepResult { params, summary: Summary::new(&cases, &best, objective, score_type) })
+            .collect();
+        print_table(&results, objective);
+        Ok(results)
+    }
+}
+
+/// 組み合わせごとの集計を表にする。最も良い行には "*" を付ける (端末なら緑)。
+pub fn print_table(results: &[SweepResult], objective: Objective) {
+    let best = best_index(results, objective);
+    let mut names: Vec<&str> = Vec::new();
+    for r in results {
+        for (k, _) in &r.params {
+            if !names.contains(&k.as_str()) {
+                names.push(k);
+            }
+        }
+    }
+    let mut rows: Vec<Vec<String>> = vec![["", "#"]
+        .into_iter()
+        .map(String::from)
+        .chain(names.iter().map(|n| n.to_string()))
+        .chain(["TOTAL", "AVG", "REL", "FAILED", "MAX_ELAPSED"].map(String::from))
+        .collect()];
+    for (i, r) in results.iter().enumerate() {
+        let mut row = vec![if Some(i) == best { "*" } else { "" }.to_string(), (i + 1).to_string()];
+        for name in &names {
+            let v = r.params.iter().find(|(k, _)| k == name).map_or("-", |(_, v)| v.as_str());
+            row.push(v.to_string());
+        }
+        let s = &r.summary;
+        row.push(format_with_commas(s.total));
+        row.push(s.mean.map_or("-".to_string(), |m| format_with_commas(format!("{:.2}", m))));
+        row.push(format_with_commas(s.relative_total));
+        row.push(format!("{}/{}", s.failed.len(), s.cases));
+        row.push(format!("{:.2}s", s.max_elapsed));
+        rows.push(row);
+    }
+
+    let color = io::stdout().is_terminal();
+    for (i, line) in align_rows(&rows).iter().enumerate() {
+        if color && i > 0 && best == Some(i - 1) {
+            println!("{}", paint(line, GREEN));
+        } else {
+            println!("{}", line);
+        }
+    }
+}
+
+/// 表の各列を右寄せで揃えた行にする。
+pub(crate) fn align_rows(rows: &[Vec<String>]) -> Vec<String> {
+    let columns = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..columns)
+        .map(|c| rows.iter().filter_map(|r| r.get(c)).map(|v| v.chars().count()).max().unwrap_or(0))
+        .collect();
+    rows.iter()
+        .map(|row| {
+            row.iter()
+                .zip(&widths)
+                .map(|(v, w)| format!("{:>w$}", v, w = w))
+                .collect::<Vec<_>>()
+                .join("  ")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_combinations() {
+        let config: SweepConfig = toml::from_str(
+            r#"
+            grid = { A = [1, 2], B = ["x", "y"] }
+            list = [{ A = 0.5 }]
+            "#,
+        )
+        .unwrap();
+        let combos: Vec<String> = config.combinations().iter().map(format_params).collect();
+        assert_eq!(combos, vec!["A=0.5", "A=1 B=x", "A=1 B=y", "A=2 B=x", "A=2 B=y"]);
+        assert!(SweepConfig::default().combinations().is_empty());
+    }
+
+    #[test]
+    fn test_add_param_arg() {
+        let mut config = SweepConfig::default();
+        config.add_param_arg("T0=100, 200").unwrap();
+        assert_eq!(config.combinations().iter().map(format_params).collect::<Vec<_>>(), vec!["T0=100", "T0=200"]);
+        assert!(config.add_param_arg("T0").is_err());
+        assert!(config.add_param_arg("=1").is_err());
+    }
+
+    #[test]
+    fn test_best_index() {
+        use crate::stats::Summary;
+        use crate::{Score, ScoreType};
+        let summary = |total: i64, failed: usize| {
+            let mut s = Summary::new(&[], &BestScores::default(), Objective::Max, ScoreType::Int);
+            s.total = Score::Int(total);
+            s.failed = vec![0; failed];
+            s
+        };
+        let results: Vec<SweepResult> = [(100, 0), (300, 1), (200, 0)]
+            .into_iter()
+            .map(|(t, f)| SweepResult { params: Vec::new(), summary: summary(t, f) })
+            .collect();
+        assert_eq!(best_index(&results, Objective::Max), Some(2));
+        assert_eq!(best_index(&results, Objective::Min), Some(0));
+    }
+}
@@ -0,0 +1,675 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::sweep::ParamValue;
+use crate::{format_with_commas, hash, Heu, Objective, Score, ScoreType};
+
+/// 探索するパラメータの範囲。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ParamSpec {
+    /// min 以上 max 以下の整数。log = true なら対数スケールで探索する。
+    Int {
+        min: i64,
+        max: i64,
+        #[serde(default)]
+        log: bool,
+    },
+    /// min 以上 max 以下の小数。log = true なら対数スケールで探索する。
+    Float {
+        min: f64,
+        max: f64,
+        #[serde(default)]
+        log: bool,
+    },
+    /// 候補のいずれか。
+    Choice { values: Vec<ParamValue> },
+}
+
+/// 小数のパラメータは有効数字6桁に丸める (ログや出力を読みやすくするため)。
+fn round_significant(v: f64) -> f64 {
+    if v == 0.0 || !v.is_finite() {
+        return v;
+    }
+    let digits = 6 - v.abs().log10().ceil() as i32;
+    let f = 10f64.powi(digits);
+    (v * f).round() / f
+}
+
+/// 標準正規分布に従う乱数 (Box-Muller 法)。
+fn gaussian(rng: &mut StdRng) -> f64 {
+    let u1: f64 = rng.gen_range(f64::EPSILON..1.0);
+    let u2: f64 = rng.gen();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+}
+
+impl ParamSpec {
+    fn validate(&self, name: &str) -> io::Result<()> {
+        let invalid = |reason: &str| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("invalid tune.params.{}: {}", name, reason))
+        };
+        let (min, max, log) = match *self {
+            ParamSpec::Int { min, max, log } => (min as f64, max as f64, log),
+            ParamSpec::Float { min, max, log } => (min, max, log),
+            ParamSpec::Choice { ref values } => {
+                return if values.is_empty() { Err(invalid("values is empty")) } else { Ok(()) };
+            }
+        };
+        if min > max || min.is_nan() || max.is_nan() {
+            return Err(invalid("min is greater than max"));
+        }
+        if log && min <= 0.0 {
+            return Err(invalid("log scale needs min > 0"));
+        }
+        Ok(())
+    }
+
+    /// 数値のパラメータを [0, 1] に写す区間 (下端, 上端, 対数か)。
+    fn range(&self) -> Option<(f64, f64, bool)> {
+        match *self {
+            // 端の値も同じ幅で選ばれるように 0.5 ずつ広げる
+            ParamSpec::Int { min, max, log: false } => Some((min as f64 - 0.5, max as f64 + 0.5, false)),
+            ParamSpec::Int { min, max, log: true } => Some((min as f64, max as f64, true)),
+            ParamSpec::Float { min, max, log } => Some((min, max, log)),
+            ParamSpec::Choice { .. } => None,
+        }
+    }
+
+    fn value_at(&self, u: f64) -> ParamValue {
+        let Some((lo, hi, log)) = self.range() else {
+            unreachable!("choice has no numeric range")
+        };
+        let x = if log { (lo.ln() + u * (hi.ln() - lo.ln())).exp() } else { lo + u * (hi - lo) };
+        match *self {
+            ParamSpec::Int { min, max, .. } => ParamValue::Int((x.round() as i64).clamp(min, max)),
+            ParamSpec::Float { min, max, .. } => ParamValue::Float(round_significant(x).clamp(min, max)),
+            ParamSpec::Choice { .. } => unreachable!(),
+        }
+    }
+
+    fn to_unit(&self, value: &ParamValue) -> Option<f64> {
+        let (lo, hi, log) = self.range()?;
+        let x = match *value {
+            ParamValue::Int(v) => v as f64,
+            ParamValue::Float(v) => v,
+            _ => return None,
+        };
+        if hi <= lo {
+            return Some(0.5);
+        }
+        let u = if log { (x.ln() - lo.ln()) / (hi.ln() - lo.ln()) } else { (x - lo) / (hi - lo) };
+        Some(u.clamp(0.0, 1.0))
+    }
+
+    /// 一様ランダムに選ぶ (対数スケールなら対数上で一様)。
+    pub fn sample(&self, rng: &mut StdRng) -> ParamValue {
+        match self {
+            ParamSpec::Choice { values } => values.choose(rng).cloned().expect("values is not empty"),
+            _ => self.value_at(rng.gen()),
+        }
+    }
+
+    /// 近傍の値を選ぶ。数値は範囲の 1 割程度ずらし、候補は別の値に変える。
+    /// 変えられない場合 (範囲が1点など) は元の値を返す。
+    pub fn neighbor(&self, value: &ParamValue, rng: &mut StdRng) -> ParamValue {
+        if let ParamSpec::Choice { values } = self {
+            let others: Vec<&ParamValue> = values.iter().filter(|v| *v != value).collect();
+            return others.choose(rng).map_or_else(|| value.clone(), |v| (*v).clone());
+        }
+        let Some(u) = self.to_unit(value) else {
+            return self.sample(rng);
+        };
+        for _ in 0..10 {
+            let v = self.value_at((u + 0.1 * gaussian(rng)).clamp(0.0, 1.0));
+            if v != *value {
+                return v;
+            }
+        }
+        // 幅の狭い整数は端で動けないことがあるので隣の値にする
+        match (self, value) {
+            (ParamSpec::Int { min, max, .. }, ParamValue::Int(v)) if v < max => ParamValue::Int(v + 1),
+            (ParamSpec::Int { min, .. }, ParamValue::Int(v)) if v > min => ParamValue::Int(v - 1),
+            _ => value.clone(),
+        }
+    }
+}
+
+/// 探索方法。
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Strategy {
+    /// 全ての試行をランダムに選ぶ。
+    Random,
+    /// 最初の 1/4 をランダムに選び、残りは最良のパラメータの近傍を試す (山登り)。
+    #[default]
+    Local,
+    /// ランダムに選んだ候補を少ないケースで評価し、上位 1/eta をケースを増やして評価し直すことを繰り返す。
+    Halving,
+}
+
+impl FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(Strategy::Random),
+            "local" => Ok(Strategy::Local),
+            "halving" => Ok(Strategy::Halving),
+            _ => Err(format!("unknown strategy '{}' (expected random, local or halving)", s)),
+        }
+    }
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Strategy::Random => "random",
+            Strategy::Local => "local",
+            Strategy::Halving => "halving",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// `[tune]` の設定。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TuneConfig {
+    /// 探索するパラメータ。環境変数としてソリューションに渡す。
+    pub params: BTreeMap<String, ParamSpec>,
+    pub strategy: Strategy,
+    /// 試行回数。halving では最初に選ぶ候補の数。
+    pub trials: usize,
+    /// 乱数のシード。同じシードなら同じ順に試すので、試行ログから再開できる。
+    pub seed: u64,
+    /// halving で1段ごとに残す割合の逆数。
+    pub eta: usize,
+    /// 最良のパラメータの出力先。省略時は `{out_dir}/tune/best.env`。
+    pub output: Option<String>,
+    /// 入力ファイルの1行目の各トークンに付ける特徴量の名前 ("_" は使わない)。
+    /// 空でなければ、特徴量の区間ごとに最も良いパラメータを表にする。
+    pub features: Vec<String>,
+    /// 特徴量ごとの区間の区切り。省略した特徴量は値の分布から `bins` 等分する。
+    pub buckets: BTreeMap<String, Vec<f64>>,
+    pub bins: usize,
+    /// 区間ごとのパラメータを返す Rust の関数の出力先。省略時は `{out_dir}/tune/table.rs`。
+    pub table: Option<String>,
+}
+
+impl Default for TuneConfig {
+    fn default() -> Self {
+        Self {
+            params: BTreeMap::new(),
+            strategy: Strategy::Local,
+            trials: 50,
+            seed: 0,
+            eta: 3,
+            output: None,
+            features: Vec::new(),
+            buckets: BTreeMap::new(),
+            bins: 3,
+            table: None,
+        }
+    }
+}
+
+/// パラメータ名と値の組。
+pub type Assignment = BTreeMap<String, ParamValue>;
+
+fn format_assignment(params: &Assignment) -> String {
+    params.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join(" ")
+}
+
+/// 1回の試行。ケースごとのスコアを持つので、後から別の集計もできる。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Trial {
+    pub params: Assignment,
+    /// ケースごとのスコア。失敗したケースは null。
+    pub scores: BTreeMap<u32, Option<Score>>,
+    /// 実行バイナリのハッシュ ([`hash::command_hash`])。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bin_hash: Option<String>,
+}
+
+impl Trial {
+    pub fn failed(&self) -> usize {
+        self.scores.values().filter(|s| s.is_none()).count()
+    }
+
+    pub fn total(&self, score_type: ScoreType) -> Score {
+        self.scores.values().flatten().fold(Score::zero(score_type), |acc, &s| acc + s)
+    }
+
+    /// 失敗したケースが少ない方、同じならスコア合計が良い方を大きいとする。
+    pub fn compare(&self, other: &Trial, objective: Objective, score_type: ScoreType) -> Ordering {
+        other
+            .failed()
+            .cmp(&self.failed())
+            .then_with(|| objective.compare(self.total(score_type), other.total(score_type)))
+    }
+
+    fn same_run(&self, params: &Assignment, cases: &[u32], bin_hash: &str) -> bool {
+        // 表示が同じ値は同じ環境変数になるので同じパラメータとみなす
+        self.bin_hash.as_deref() == Some(bin_hash)
+            && self.scores.len() == cases.len()
+            && cases.iter().all(|c| self.scores.contains_key(c))
+            && format_assignment(&self.params) == format_assignment(params)
+    }
+}
+
+/// `{out_dir}/tune/trials.jsonl` に1行1試行で追記する試行ログ。
+/// 同じバイナリ・同じパラメータ・同じケースの試行はログの結果を使い回す。
+pub struct TrialLog {
+    path: PathBuf,
+    trials: Vec<Trial>,
+}
+
+impl TrialLog {
+    pub fn path(out_dir: &str) -> PathBuf {
+        Path::new(out_dir).join("tune").join("trials.jsonl")
+    }
+
+    /// ログを読み込む。無ければ空。中断で書きかけになった最後の行は捨てる。
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let mut trials = Vec::new();
+        if path.exists() {
+            let content = fs::read_to_string(path)?;
+            let lines: Vec<&str> = content.lines().filter(|l| !l.trim().is_empty()).collect();
+            for (i, line) in lines.iter().enumerate() {
+                match serde_json::from_str(line) {
+                    Ok(t) => trials.push(t),
+                    Err(_) if i + 1 == lines.len() => {
+                        eprintln!("Ignoring incomplete last line of {}", path.display());
+                        let mut log = Self { path: path.to_path_buf(), trials };
+                        log.rewrite()?;
+                        return Ok(log);
+                    }
+                    Err(e) => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("{}:{}: {}", path.display(), i + 1, e),
+                        ))
+                    }
+                }
+            }
+        }
+        Ok(Self { path: path.to_path_buf(), trials })
+    }
+
+    pub fn trials(&self) -> &[Trial] {
+        &self.trials
+    }
+
+    pub fn find(&self, params: &Assignment, cases: &[u32], bin_hash: &str) -> Option<&Trial> {
+        self.trials.iter().find(|t| t.same_run(params, cases, bin_hash))
+    }
+
+    pub fn append(&mut self, trial: Trial) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = fs::OpenOptions::new().create(true).append(true).open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(&trial)?)?;
+        self.trials.push(trial);
+        Ok(())
+    }
+
+    fn rewrite(&mut self) -> io::Result<()> {
+        let mut content = String::new();
+        for t in &self.trials {
+            content.push_str(&serde_json::to_string(t)?);
+            content.push('\n');
+        }
+        fs::write(&self.path, content)
+    }
+}
+
+/// successive halving の各段の (候補数, ケース数)。最後の段は1候補を全ケースで評価する。
+pub fn halving_rungs(trials: usize, eta: usize, cases: usize) -> Vec<(usize, usize)> {
+    let eta = eta.max(2);
+    let mut counts = vec![trials.max(1)];
+    while *counts.last().unwrap() > 1 {
+        let c = *counts.last().unwrap();
+        counts.push(c.div_ceil(eta));
+    }
+    let n = counts.len();
+    counts
+        .into_iter()
+        .enumerate()
+        .map(|(k, c)| {
+            let div = eta.saturating_pow((n - 1 - k) as u32);
+            (c, cases.div_ceil(div).max(1))
+        })
+        .collect()
+}
+
+/// 最良のパラメータを書き出す。拡張子が .json なら JSON オブジェクト、それ以外は "NAME=VALUE" の行。
+pub fn write_params(path: &Path, params: &Assignment) -> io::Result<()> {
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    let content = if path.extension().is_some_and(|e| e == "json") {
+        format!("{}\n", serde_json::to_string_pretty(params)?)
+    } else {
+        params.iter().map(|(k, v)| format!("{}={}\n", k, v)).collect()
+    };
+    fs::write(path, content)
+}
+
+/// 探索中の状態。
+struct Tuner<'a> {
+    heu: &'a Heu,
+    config: &'a TuneConfig,
+    log: TrialLog,
+    /// 実行バイナリのハッシュ。違うバイナリでの試行は使い回さない。
+    bin_hash: String,
+    rng: StdRng,
+    /// 予定している試行の数と済んだ数 (表示用)。
+    planned: usize,
+    done: usize,
+    /// ログの結果を使い回した数。
+    reused: usize,
+    /// 全ケースで評価した試行のうち最良のもの。
+    best: Option<Trial>,
+}
+
+impl Tuner<'_> {
+    fn objective(&self) -> Objective {
+        self.heu.config.test.objective
+    }
+
+    fn score_type(&self) -> ScoreType {
+        self.heu.config.test.score_type
+    }
+
+    fn sample(&mut self) -> Assignment {
+        let mut params = Assignment::new();
+        for (name, spec) in &self.config.params {
+            params.insert(name.clone(), spec.sample(&mut self.rng));
+        }
+        params
+    }
+
+    /// 1つ以上のパラメータを近傍の値に変える。
+    fn neighbor(&mut self, params: &Assignment) -> Assignment {
+        let names: Vec<&String> = self.config.params.keys().collect();
+        let p = 1.0 / names.len() as f64;
+        let forced = self.rng.gen_range(0..names.len());
+        let mut ret = params.clone();
+        for (i, name) in names.into_iter().enumerate() {
+            if i == forced || self.rng.gen_bool(p) {
+                let spec = &self.config.params[name];
+                let v = match params.get(name) {
+                    Some(v) => spec.neighbor(v, &mut self.rng),
+                    None => spec.sample(&mut self.rng),
+                };
+                ret.insert(name.clone(), v);
+            }
+        }
+        ret
+    }
+
+    fn evaluate(&mut self, params: &Assignment, cases: &[u32]) -> io::Result<Trial> {
+        self.done += 1;
+        let cached = self.log.find(params, cases, &self.bin_hash).cloned();
+        let reused = cached.is_some();
+        let trial = match cached {
+            Some(t) => {
+                self.reused += 1;
+                t
+            }
+            None => {
+                let env: Vec<(String, String)> = params.iter().map(|(k, v)| (k.clone(), v.to_string())).collect();
+                let mut scores = BTreeMap::new();
+                self.heu.run_parallel(cases, &env, |r| {
+                    scores.insert(r.case, r.ok_score());
+                })?;
+                let trial = Trial { params: params.clone(), scores, bin_hash: Some(self.bin_hash.clone()) };
+                self.log.append(trial.clone())?;
+                trial
+            }
+        };
+
+        let (objective, score_type) = (self.objective(), self.score_type());
+        let full = cases.len() == self.heu.cases.len();
+        let improved = full && self.best.as_ref().is_none_or(|b| trial.compare(b, objective, score_type).is_gt());
+        if improved {
+            self.best = Some(trial.clone());
+        }
+        eprintln!(
+            "[{}/{}] {} TOTAL={} FAILED={}/{}{}{}",
+            self.done,
+            self.planned,
+            format_assignment(params),
+            format_with_commas(trial.total(score_type)),
+            trial.failed(),
+            cases.len(),
+            if reused { " (cached)" } else { "" },
+            if improved { " *" } else { "" },
+        );
+        Ok(trial)
+    }
+
+    fn random(&mut self) -> io::Result<()> {
+        let cases = self.heu.cases.clone();
+        self.planned = self.config.trials;
+        for _ in 0..self.config.trials {
+            let params = self.sample();
+            self.evaluate(&params, &cases)?;
+        }
+        Ok(())
+    }
+
+    fn local(&mut self) -> io::Result<()> {
+        let cases = self.heu.cases.clone();
+        let trials = self.config.trials;
+        self.planned = trials;
+        let init = (trials / 4).clamp(1, trials.max(1));
+        for i in 0..trials {
+            let params = match &self.best {
+                Some(best) if i >= init => {
+                    let base = best.params.clone();
+                    self.neighbor(&base)
+                }
+                _ => self.sample(),
+            };
+            self.evaluate(&params, &cases)?;
+        }
+        Ok(())
+    }
+
+    fn halving(&mut self) -> io::Result<()> {
+        let mut order = self.heu.cases.clone();
+        order.shuffle(&mut self.rng);
+        let rungs = halving_rungs(self.config.trials, self.config.eta, order.len());
+        self.planned = rungs.iter().map(|(c, _)| c).sum();
+
+        let mut candidates: Vec<Assignment> = (0..rungs[0].0).map(|_| self.sample()).collect();
+        for (k, &(_, m)) in rungs.iter().enumerate() {
+            let cases = &order[..m];
+            let mut results = Vec::with_capacity(candidates.len());
+            for params in &candidates {
+                results.push(self.evaluate(params, cases)?);
+            }
+            let (objective, score_type) = (self.objective(), self.score_type());
+            results.sort_by(|a, b| b.compare(a, objective, score_type));
+            if let Some(&(next, _)) = rungs.get(k + 1) {
+                candidates = results.into_iter().take(next).map(|t| t.params).collect();
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Heu {
+    /// `[tune]` のパラメータ空間を探索し、最良のパラメータを返してファイルに書き出す。
+    /// 試行は `{out_dir}/tune/trials.jsonl` に記録し、同じ設定で再実行するとログの結果を使って続きから探索する。
+    /// 実行バイナリが変わった場合はログの試行を使わない。`fresh` ならログを消してから始める。
+    pub fn tune(&self, fresh: bool) -> io::Result<Trial> {
+        let config = &self.config.tune;
+        if config.params.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "no tune parameters: add [tune.params] to config"));
+        }
+        for (name, spec) in &config.params {
+            spec.validate(name)?;
+        }
+        if config.trials == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "tune.trials must be positive"));
+        }
+        self.prepare()?;
+
+        let log_path = TrialLog::path(&self.config.test.out_dir);
+        if fresh && log_path.exists() {
+            fs::remove_file(&log_path)?;
+        }
+        let mut tuner = Tuner {
+            heu: self,
+            config,
+            log: TrialLog::load(&log_path)?,
+            bin_hash: hash::command_hash(&self.config.test.bin)?,
+            rng: StdRng::seed_from_u64(config.seed),
+            planned: 0,
+            done: 0,
+            reused: 0,
+            best: None,
+        };
+        match config.strategy {
+            Strategy::Random => tuner.random()?,
+            Strategy::Local => tuner.local()?,
+            Strategy::Halving => tuner.halving()?,
+        }
+        if tuner.reused > 0 {
+            eprintln!("Reused {} of {} trials from {}", tuner.reused, tuner.done, log_path.display());
+        }
+
+        let best = tuner.best.expect("at least one trial runs on all cases");
+        let output = config.output.as_ref().map_or_else(
+            || Path::new(&self.config.test.out_dir).join("tune").join("best.env"),
+            PathBuf::from,
+        );
+        write_params(&output, &best.params)?;
+        println!(
+            "Best: {} TOTAL={} FAILED={}/{}",
+            format_assignment(&best.params),
+            format_with_commas(best.total(self.config.test.score_type)),
+            best.failed(),
+            best.scores.len()
+        );
+        eprintln!("Wrote best params: {}", output.display());
+        if !config.features.is_empty() {
+            self.tune_table()?;
+        }
+        Ok(best)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn specs() -> BTreeMap<String, ParamSpec> {
+        let config: TuneConfig = toml::from_str(
+            r#"
+            [params]
+            A = { type = "int", min = 1, max = 10 }
+            B = { type = "float", min = 1, max = 10000, log = true }
+            C = { type = "choice", values = ["x", "y", 3] }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.strategy, Strategy::Local);
+        assert_eq!(config.trials, 50);
+        config.params
+    }
+
+    #[test]
+    fn test_sample_and_neighbor_stay_in_range() {
+        let specs = specs();
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut small_b = 0;
+        for _ in 0..200 {
+            for (name, spec) in &specs {
+                let v = spec.sample(&mut rng);
+                let n = spec.neighbor(&v, &mut rng);
+                for v in [&v, &n] {
+                    match (name.as_str(), v) {
+                        ("A", ParamValue::Int(a)) => assert!((1..=10).contains(a)),
+                        ("B", ParamValue::Float(b)) => {
+                            assert!((1.0..=10000.0).contains(b));
+                            small_b += (*b < 100.0) as usize;
+                        }
+                        ("C", c) => assert!(matches!(c, ParamValue::Str(_) | ParamValue::Int(3))),
+                        other => panic!("unexpected {:?}", other),
+                    }
+                }
+                assert_ne!(v, n);
+            }
+        }
+        // 対数スケールなら半分程度が 100 未満になる
+        assert!(small_b > 100, "{}", small_b);
+    }
+
+    #[test]
+    fn test_validate() {
+        let bad = [
+            ParamSpec::Int { min: 5, max: 1, log: false },
+            ParamSpec::Float { min: 0.0, max: 1.0, log: true },
+            ParamSpec::Choice { values: Vec::new() },
+        ];
+        for spec in bad {
+            assert!(spec.validate("X").is_err(), "{:?}", spec);
+        }
+        for spec in specs().values() {
+            spec.validate("X").unwrap();
+        }
+    }
+
+    #[test]
+    fn test_halving_rungs() {
+        assert_eq!(halving_rungs(27, 3, 100), vec![(27, 4), (9, 12), (3, 34), (1, 100)]);
+        assert_eq!(halving_rungs(10, 3, 5), vec![(10, 1), (4, 1), (2, 2), (1, 5)]);
+        assert_eq!(halving_rungs(1, 3, 5), vec![(1, 5)]);
+    }
+
+    #[test]
+    fn test_trial_log_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("heu_tune_log_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = TrialLog::path(dir.to_str().unwrap());
+        let params: Assignment = [("T".to_string(), ParamValue::Float(1.5))].into_iter().collect();
+        let trial = Trial {
+            params: params.clone(),
+            scores: [(0, Some(Score::Int(10))), (1, None)].into_iter().collect(),
+            bin_hash: Some("a".to_string()),
+        };
+
+        let mut log = TrialLog::load(&path).unwrap();
+        log.append(trial.clone()).unwrap();
+        // 中断で書きかけになった行
+        fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(b"{\"params\":").unwrap();
+
+        let log = TrialLog::load(&path).unwrap();
+        assert_eq!(log.trials(), std::slice::from_ref(&trial));
+        assert!(log.find(&params, &[1, 0], "a").is_some());
+        assert!(log.find(&params, &[0], "a").is_none());
+        // バイナリが変わったら使い回さない
+        assert!(log.find(&params, &[1, 0], "b").is_none());
+        assert_eq!(trial.failed(), 1);
+        assert_eq!(trial.total(ScoreType::Int), Score::Int(10));
+        assert_eq!(TrialLog::load(&path).unwrap().trials().len(), 1);
+
+        let out = dir.join("best.env");
+        write_params(&out, &params).unwrap();
+        assert_eq!(fs::read_to_string(&out).unwrap(), "T=1.5\n");
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,260 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::baseline::{self, CaseDiff};
+use crate::{format_with_commas, process, Heu, Objective, Summary};
+
+/// ファイルの変更を確認する間隔。
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// `[watch]`: `cargo heu --watch` で監視するファイル。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchConfig {
+    /// 監視するファイルの glob (`*`, `?`, `**`)。`test.bin` などと同じくカレントディレクトリからの相対パス。
+    pub paths: Vec<String>,
+    /// 最後の変更からこの秒数だけ変更が無ければ再実行する。
+    pub debounce: f64,
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self { paths: vec!["src/**/*.rs".to_string(), "Cargo.toml".to_string()], debounce: 0.3 }
+    }
+}
+
+/// `path` が glob `pattern` に一致するか。区切りは "/"。
+/// `*` と `?` は区切りをまたがず、`**` は0個以上のディレクトリに一致する。
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    fn segments(s: &str) -> Vec<&str> {
+        s.split('/').filter(|p| !p.is_empty() && *p != ".").collect()
+    }
+    fn match_segments(pattern: &[&str], path: &[&str]) -> bool {
+        match pattern.split_first() {
+            None => path.is_empty(),
+            Some((&"**", rest)) => (0..=path.len()).any(|i| match_segments(rest, &path[i..])),
+            Some((p, rest)) => {
+                path.split_first().is_some_and(|(s, tail)| match_segment(p, s) && match_segments(rest, tail))
+            }
+        }
+    }
+    fn match_segment(pattern: &str, s: &str) -> bool {
+        let p: Vec<char> = pattern.chars().collect();
+        let s: Vec<char> = s.chars().collect();
+        // dp[j]: パターンの先頭 i 文字が s の先頭 j 文字に一致するか
+        let mut dp = vec![false; s.len() + 1];
+        dp[0] = true;
+        for &c in &p {
+            let mut next = vec![false; s.len() + 1];
+            for j in 0..=s.len() {
+                next[j] = match c {
+                    '*' => dp[j] || (j > 0 && next[j - 1]),
+                    '?' => j > 0 && dp[j - 1],
+                    c => j > 0 && dp[j - 1] && s[j - 1] == c,
+                };
+            }
+            dp = next;
+        }
+        dp[s.len()]
+    }
+    match_segments(&segments(pattern), &segments(path))
+}
+
+/// glob のうちワイルドカードを含まない先頭のディレクトリ。ここから下を探す。
+fn glob_base(pattern: &str) -> PathBuf {
+    let mut base = PathBuf::from(".");
+    let parts: Vec<&str> = pattern.split('/').collect();
+    for part in &parts[..parts.len() - 1] {
+        if part.contains(['*', '?']) {
+            break;
+        }
+        base.push(part);
+    }
+    base
+}
+
+/// 監視するファイルと最終更新時刻・サイズ。
+pub type Snapshot = BTreeMap<PathBuf, (SystemTime, u64)>;
+
+/// `patterns` に一致するファイルを集める。隠しディレクトリと `target` の下は探さない。
+pub fn snapshot(patterns: &[String]) -> Snapshot {
+    fn walk(dir: &Path, patterns: &[String], ret: &mut Snapshot) {
+        let Ok(entries) = fs::read_dir(dir) else { return };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(meta) = entry.metadata() else { continue };
+            if meta.is_dir() {
+                let name = entry.file_name();
+                let name = name.to_string_lossy();
+                if !name.starts_with('.') && name != "target" {
+                    walk(&path, patterns, ret);
+                }
+            } else {
+                let rel = path.strip_prefix(".").unwrap_or(&path).to_string_lossy().replace('\\', "/");
+                if patterns.iter().any(|p| glob_match(p, &rel)) {
+                    let modified = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                    ret.insert(path, (modified, meta.len()));
+                }
+            }
+        }
+    }
+    let mut ret = Snapshot::new();
+    for pattern in patterns {
+        if pattern.contains(['*', '?']) {
+            walk(&glob_base(pattern), std::slice::from_ref(pattern), &mut ret);
+        } else if let Ok(meta) = fs::metadata(pattern) {
+            // ワイルドカードが無ければそのファイルだけ見る
+            let path = Path::new(".").join(pattern.trim_start_matches("./"));
+            ret.insert(path, (meta.modified().unwrap_or(SystemTime::UNIX_EPOCH), meta.len()));
+        }
+    }
+    ret
+}
+
+/// 追加・削除・更新されたファイル。
+pub fn changed_paths(before: &Snapshot, after: &Snapshot) -> Vec<PathBuf> {
+    let mut ret: Vec<PathBuf> = after.iter().filter(|(p, v)| before.get(*p) != Some(v)).map(|(p, _)| p.clone()).collect();
+    ret.extend(before.keys().filter(|p| !after.contains_key(*p)).cloned());
+    ret.sort();
+    ret
+}
+
+impl Heu {
+    /// `[watch]` のファイルを監視し、変更されるたびにビルドと選択したケースの実行をやり直す。
+    /// 実行中に変更された場合はその実行を中断する。実行が終わるたびに前回の合計と並べて表示する。
+    /// Ctrl-C で終了する。
+    pub fn watch(&self) -> io::Result<()> {
+        let patterns = &self.config.watch.paths;
+        if patterns.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "watch.paths is empty"));
+        }
+        let debounce = Duration::from_secs_f64(self.config.watch.debounce.max(0.0));
+        let objective = self.config.test.objective;
+        self.watching.store(true, Ordering::SeqCst);
+        let mut files = snapshot(patterns);
+        eprintln!("Watching {} files ({})", files.len(), patterns.join(", "));
+        let mut prev: Option<Summary> = None;
+        for iteration in 1.. {
+            process::reset_cancel();
+            eprintln!("[watch #{}] Building and running {} cases", iteration, self.cases.len());
+            let next = thread::scope(|s| {
+                let mut runner = Some(s.spawn(|| self.execute()));
+                let mut current = files.clone();
+                loop {
+                    thread::sleep(POLL_INTERVAL);
+                    // Ctrl-C で終了する。実行中なら途中までの結果の保存を待つ
+                    if process::is_interrupted() {
+                        if let Some(r) = runner.take() {
+                            let _ = r.join();
+                        }
+                        return None;
+                    }
+                    if runner.as_ref().is_some_and(|r| r.is_finished()) {
+                        let result = runner.take().unwrap().join().unwrap();
+                        match result {
+                            Ok(summary) => {
+                                print_iteration(iteration, &summary, prev.as_ref(), objective);
+                                prev = Some(summary);
+                            }
+                            Err(e) => eprintln!("[watch #{}] Error: {}", iteration, e),
+                        }
+                        eprintln!("[watch] Waiting for changes...");
+                    }
+                    let next = snapshot(patterns);
+                    if next == current {
+                        continue;
+                    }
+                    // 保存が続く間は待つ
+                    current = next;
+                    loop {
+                        thread::sleep(debounce);
+                        let next = snapshot(patterns);
+                        if next == current {
+                            break;
+                        }
+                        current = next;
+                    }
+                    let changed = changed_paths(&files, &current);
+                    if changed.is_empty() {
+                        continue;
+                    }
+                    let names: Vec<String> = changed.iter().map(|p| p.display().to_string()).collect();
+                    eprintln!("[watch] Changed: {}", names.join(" "));
+                    if let Some(r) = runner.take() {
+                        process::cancel();
+                        if let Ok(Ok(summary)) = r.join() {
+                            print_iteration(iteration, &summary, prev.as_ref(), objective);
+                            prev = Some(summary);
+                        } else {
+                            eprintln!("[watch #{}] Cancelled", iteration);
+                        }
+                    }
+                    break Some(current);
+                }
+            });
+            match next {
+                Some(next) => files = next,
+                None => break,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 1回分の合計を前回の合計と並べて表示する。
+fn print_iteration(iteration: usize, summary: &Summary, prev: Option<&Summary>, objective: Objective) {
+    let mut line = format!("WATCH #{}: TOTAL={}", iteration, format_with_commas(summary.total));
+    if let Some(p) = prev {
+        let d = CaseDiff::new(Some(summary.total), Some(p.total), objective);
+        line.push_str(&format!(" (prev {}) VS[{}]", format_with_commas(p.total), baseline::format_diff(&d)));
+    }
+    line.push_str(&format!(" REL={}", format_with_commas(summary.relative_total)));
+    if let Some(p) = prev {
+        line.push_str(&format!(" (prev {})", format_with_commas(p.relative_total)));
+    }
+    line.push_str(&format!(" FAILED={}/{}", summary.failed.len(), summary.cases));
+    if let Some(p) = prev {
+        line.push_str(&format!(" (prev {}/{})", p.failed.len(), p.cases));
+    }
+    println!("{}", line);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("src/**/*.rs", "src/main.rs"));
+        assert!(glob_match("src/**/*.rs", "src/a/b/c.rs"));
+        assert!(glob_match("./src/*.rs", "src/lib.rs"));
+        assert!(!glob_match("src/*.rs", "src/a/lib.rs"));
+        assert!(!glob_match("src/**/*.rs", "tools/src/main.rs"));
+        assert!(glob_match("Cargo.toml", "Cargo.toml"));
+        assert!(glob_match("**/mod?.rs", "a/mod1.rs"));
+        assert!(!glob_match("**/mod?.rs", "a/mod12.rs"));
+        assert_eq!(glob_base("src/**/*.rs"), Path::new("./src"));
+        assert_eq!(glob_base("Cargo.toml"), Path::new("."));
+    }
+
+    #[test]
+    fn test_changed_paths() {
+        let t = |s: u64| SystemTime::UNIX_EPOCH + Duration::from_secs(s);
+        let before: Snapshot = [("a", (t(1), 1)), ("b", (t(1), 1)), ("c", (t(1), 1))]
+            .into_iter()
+            .map(|(p, v)| (PathBuf::from(p), v))
+            .collect();
+        let mut after = before.clone();
+        after.remove(Path::new("a"));
+        after.insert(PathBuf::from("b"), (t(2), 1));
+        after.insert(PathBuf::from("d"), (t(1), 1));
+        assert_eq!(changed_paths(&before, &after), ["a", "b", "d"].map(PathBuf::from));
+        assert!(changed_paths(&before, &before).is_empty());
+    }
+}